@@ -0,0 +1,98 @@
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use human_bytes::human_bytes;
+use yansi::Paint;
+
+use crate::hmm::cache::{Cache, CachedRepo, CachedZip};
+
+pub fn list_cache() -> Result<()> {
+    let cache = Cache::user()?;
+    let zips = cache.list_zips()?;
+    let repos = cache.list_repos()?;
+    println!("Cache folder: {}", cache.root().display().bold());
+
+    if zips.is_empty() && repos.is_empty() {
+        println!("The cache is empty");
+        return Ok(());
+    }
+
+    for zip in zips.iter() {
+        println!(
+            "{} {} {} {}",
+            zip.name.bold(),
+            zip.version.green(),
+            human_bytes(zip.size as f64).dim(),
+            last_used(zip.last_used).dim()
+        );
+    }
+    for repo in repos.iter() {
+        println!(
+            "{} {} {} {}",
+            repo.url.bold(),
+            "git".green(),
+            human_bytes(repo.size as f64).dim(),
+            last_used(repo.last_used).dim()
+        );
+    }
+
+    println!(
+        "{} zip(s) and {} git repo(s), {} total",
+        zips.len().bold(),
+        repos.len().bold(),
+        human_bytes((zips_size(&zips) + repos_size(&repos)) as f64).bold()
+    );
+    Ok(())
+}
+
+fn last_used(time: SystemTime) -> String {
+    let days = SystemTime::now()
+        .duration_since(time)
+        .unwrap_or_default()
+        .as_secs()
+        / 60
+        / 60
+        / 24;
+    format!("(last used {} day(s) ago)", days)
+}
+
+pub fn clear_cache() -> Result<()> {
+    let cache = Cache::user()?;
+    let freed = cache.clear()?;
+    println!(
+        "Removed {}, freeing {}",
+        cache.root().display().bold(),
+        human_bytes(freed as f64).bold()
+    );
+    Ok(())
+}
+
+/// Removes zips and git repos that haven't been installed from in `days` days
+pub fn prune_cache(days: u64) -> Result<()> {
+    let cache = Cache::user()?;
+    let max_age = Duration::from_secs(days * 24 * 60 * 60);
+    let zips = cache.prune(max_age)?;
+    let repos = cache.prune_repos(max_age)?;
+
+    for zip in zips.iter() {
+        println!("Removed {} {}", zip.name.bold(), zip.version.red());
+    }
+    for repo in repos.iter() {
+        println!("Removed {} {}", repo.url.bold(), "git".red());
+    }
+    println!(
+        "{} zip(s) and {} git repo(s) pruned, freeing {}",
+        zips.len().bold(),
+        repos.len().bold(),
+        human_bytes((zips_size(&zips) + repos_size(&repos)) as f64).bold()
+    );
+    Ok(())
+}
+
+fn zips_size(zips: &[CachedZip]) -> u64 {
+    zips.iter().map(|zip| zip.size).sum()
+}
+
+fn repos_size(repos: &[CachedRepo]) -> u64 {
+    repos.iter().map(|repo| repo.size).sum()
+}
@@ -1,10 +1,13 @@
-use std::{fs::File, path::Path};
+use std::fs::File;
+use std::path::{Path, PathBuf};
 
+use crate::commands::{install_command, OutputFormat};
 use crate::hmm::dependencies::Dependancies;
-use crate::hmm::haxelib::{self, Haxelib, HaxelibType};
-use anyhow::{anyhow, Context, Result};
+use crate::hmm::haxelib::{Haxelib, HaxelibType};
+use crate::hmm::resolver::{self, Conflict, DependencyGraph};
+use anyhow::{anyhow, Result};
 use console::Emoji;
-use gix::hash::Prefix;
+use serde::Serialize;
 use std::io::Read;
 use yansi::Paint;
 
@@ -16,14 +19,16 @@ pub struct HaxelibStatus<'a> {
 }
 
 // First, define the install type enum
-#[derive(Debug, PartialEq)]
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
 pub enum InstallType {
-    Missing,          // Needs to be installed
-    MissingGit,       // Needs to be cloned
-    Outdated,         // Installed but wrong version
-    AlreadyInstalled, // Correctly installed
-    Conflict,         // Version conflicts between dependencies
-    NotLocked,        // Version in hmm.json isn't locked to anything, prompt to lock?
+    Missing,           // Needs to be installed
+    MissingGit,        // Needs to be cloned
+    Outdated,          // Installed but wrong version
+    AlreadyInstalled,  // Correctly installed
+    Dirty,             // Git worktree has local changes
+    OutdatedSubmodule, // A git submodule isn't cloned, or isn't at the commit its parent records
+    NotLocked,         // Version in hmm.json isn't locked to anything, prompt to lock?
 }
 
 impl<'a> HaxelibStatus<'a> {
@@ -42,48 +47,211 @@ impl<'a> HaxelibStatus<'a> {
     }
 }
 
-pub fn check(deps: &Dependancies) -> Result<()> {
-    match compare_haxelib_to_hmm(deps)? {
-        installs => {
+/// A single status, as printed by `check --format json`
+#[derive(Serialize)]
+struct StatusReport<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    haxelib_type: &'a HaxelibType,
+    install_type: &'a InstallType,
+    wants: Option<&'a str>,
+    installed: Option<&'a str>,
+}
+
+impl<'a> From<&'a HaxelibStatus<'a>> for StatusReport<'a> {
+    fn from(status: &'a HaxelibStatus<'a>) -> Self {
+        // correctly installed statuses only fill in `wants`, since it's the same thing
+        let installed = match status.install_type {
+            InstallType::AlreadyInstalled => status.wants.as_deref(),
+            _ => status.installed.as_deref(),
+        };
+
+        StatusReport {
+            name: &status.lib.name,
+            haxelib_type: &status.lib.haxelib_type,
+            install_type: &status.install_type,
+            wants: status.wants.as_deref(),
+            installed,
+        }
+    }
+}
+
+/// Prints the install status of everything in hmm.json,
+/// erroring if anything isn't installed at the correct version
+pub fn check(deps: &Dependancies, json_path: &Path, format: OutputFormat) -> Result<()> {
+    let installs = match format {
+        OutputFormat::Text => compare_haxelib_to_hmm(deps, json_path)?,
+        OutputFormat::Json => deps
+            .dependencies
+            .iter()
+            .map(|haxelib| check_dependency(haxelib, json_path))
+            .collect::<Result<Vec<_>>>()?,
+    };
+    let installed_count = installs
+        .iter()
+        .filter(|i| i.install_type == InstallType::AlreadyInstalled)
+        .count();
+    let graph = resolver::resolve(deps, json_path)?;
+
+    match format {
+        OutputFormat::Json => {
+            let reports: Vec<StatusReport> = installs.iter().map(StatusReport::from).collect();
+            println!("{}", serde_json::to_string_pretty(&reports)?);
+        }
+        OutputFormat::Text => {
             println!(
                 "{} / {} dependencie(s) are installed at the correct versions",
-                installs
-                    .iter()
-                    .filter(|i| i.install_type == InstallType::AlreadyInstalled)
-                    .count()
-                    .bold(),
+                installed_count.bold(),
                 deps.dependencies.len().bold()
             );
+
+            print_transitive_dependencies(&graph);
+            print_conflicts(&graph);
         }
     }
+
+    let not_installed = installs.len() - installed_count;
+    if not_installed > 0 {
+        return Err(anyhow!(
+            "{} dependencie(s) are not installed at the correct versions",
+            not_installed.bold()
+        ));
+    }
+
+    // install refuses to go ahead with these, so they can't count as a passing check
+    let unresolved = graph.unresolved_conflicts().count();
+    if unresolved > 0 {
+        return Err(anyhow!(
+            "{} version conflict(s) need resolving",
+            unresolved.bold()
+        ));
+    }
     Ok(())
 }
 
-pub fn compare_haxelib_to_hmm(deps: &Dependancies) -> Result<Vec<HaxelibStatus>> {
+fn print_conflicts(graph: &DependencyGraph) {
+    if graph.conflicts.is_empty() {
+        return;
+    }
+
+    println!();
+    println!("{}", "Version conflicts:".bold());
+    for conflict in graph.conflicts.iter() {
+        match conflict.overridden {
+            true => println!(
+                "{} {}",
+                conflict.name.yellow().bold(),
+                "is overridden by hmm.json".yellow()
+            ),
+            false => println!(
+                "{} {}",
+                conflict.name.red().bold(),
+                "has conflicting versions".red()
+            ),
+        }
+        for line in describe_conflict(conflict) {
+            println!("  {}", line);
+        }
+    }
+
+    let unresolved = graph.unresolved_conflicts().count();
+    if unresolved > 0 {
+        println!(
+            "{} conflict(s) need resolving, either change the versions or add `\"override\": true` to the library in hmm.json",
+            unresolved.bold()
+        );
+    }
+}
+
+/// One line for hmm.json (if it pins a version) and one for each library requiring a version
+pub fn describe_conflict(conflict: &Conflict) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let Some(pinned) = &conflict.pinned {
+        lines.push(format!("hmm.json wants {}", pinned));
+    }
+    for requirement in conflict.requirements.iter() {
+        lines.push(format!(
+            "{} wants {}",
+            requirement.required_by, requirement.version
+        ));
+    }
+    lines
+}
+
+fn print_transitive_dependencies(graph: &DependencyGraph) {
+    if graph.transitive.is_empty() {
+        return;
+    }
+
+    println!();
+    println!("{}", "Transitive dependencies (not in hmm.json):".bold());
+    for transitive in graph.transitive.iter() {
+        let required_by: Vec<String> = transitive
+            .required_by
+            .iter()
+            .map(|r| match r.version.is_empty() {
+                true => r.required_by.clone(),
+                false => format!("{} ({})", r.required_by, r.version),
+            })
+            .collect();
+
+        match transitive.installed {
+            true => println!(
+                "{} required by {} {}",
+                transitive.haxelib.name.green().bold(),
+                required_by.join(", "),
+                Emoji("✅", "[✔️]")
+            ),
+            false => println!(
+                "{} {} required by {}",
+                transitive.haxelib.name.red().bold(),
+                "is not installed,".red(),
+                required_by.join(", ")
+            ),
+        }
+    }
+
+    let missing = graph.missing().count();
+    if missing > 0 {
+        println!(
+            "{} transitive dependencie(s) are missing, `hmm-rs install` will install them",
+            missing.bold()
+        );
+    }
+}
+
+pub fn compare_haxelib_to_hmm<'a>(
+    deps: &'a Dependancies,
+    json_path: &Path,
+) -> Result<Vec<HaxelibStatus<'a>>> {
     let mut install_status = Vec::new();
 
     for haxelib in deps.dependencies.iter() {
-        let haxelib_status = check_dependency(haxelib)?;
-        print_install_status(&haxelib_status)?;
-
-        install_status.push(haxelib_status);
-        continue;
+        install_status.push(check_haxelib(haxelib, json_path)?);
     }
 
     Ok(install_status)
 }
 
-fn check_dependency(haxelib: &Haxelib) -> Result<HaxelibStatus> {
-    // Haxelib folders replace . with , in the folder name
-    let comma_replace = haxelib.name.replace(".", ",");
-    let lib_path = Path::new(".haxelib").join(comma_replace.as_str());
-
-    // assumes an error will occur, and if not, this line will be rewritten at the end of the for loop
+/// Checks a single haxelib, and prints its install status
+pub fn check_haxelib<'a>(haxelib: &'a Haxelib, json_path: &Path) -> Result<HaxelibStatus<'a>> {
+    // assumes an error will occur, and if not, this line will be rewritten once the check is done
     println!(
         "Checking {} {}",
         haxelib.name.bold().yellow(),
         Emoji("🤔", "[...]")
     );
+    let haxelib_status = check_dependency(haxelib, json_path)?;
+    print_install_status(&haxelib_status)?;
+    Ok(haxelib_status)
+}
+
+/// Checks a single haxelib without printing anything
+pub fn check_dependency<'a>(haxelib: &'a Haxelib, json_path: &Path) -> Result<HaxelibStatus<'a>> {
+    // Haxelib folders replace . with , in the folder name
+    let comma_replace = haxelib.name.replace(".", ",");
+    let lib_path = Path::new(".haxelib").join(comma_replace.as_str());
+
     if !lib_path.exists() {
         return Ok(HaxelibStatus::new(
             haxelib,
@@ -159,41 +327,78 @@ fn check_dependency(haxelib: &Haxelib) -> Result<HaxelibStatus> {
                 }
             };
 
-            // TODO: Need to make sure this unwraps for detatched head!
-            let head_ref = repo.head_commit().unwrap();
+            let head_commit = repo.head_id()?.detach();
 
-            // If our head ref is a tag or branch, we check if we already have it in our history
-            // If it's not a tag, we check via commit id
-            let intended_commit = match repo.find_reference(haxelib.vcs_ref.as_ref().unwrap()) {
-                Ok(r) => r.id().shorten_or_id(),
-                Err(_) => Prefix::from_hex(haxelib.vcs_ref.as_ref().unwrap())?,
-            };
+            // resolved the same way checking out does, so annotated tags get peeled to their commit.
+            // A ref that doesn't resolve hasn't been fetched yet, so it can't be checked out either
+            // Without a ref, whatever is checked out counts
+            if let Some(vcs_ref) = haxelib.vcs_ref.as_ref() {
+                let intended_commit = install_command::resolve_ref(&repo, vcs_ref)
+                    .ok()
+                    .map(|resolved| resolved.commit());
+
+                if intended_commit != Some(head_commit) {
+                    return Ok(HaxelibStatus::new(
+                        haxelib,
+                        InstallType::Outdated,
+                        get_wants(haxelib),
+                        Some(head_commit.to_string()),
+                    ));
+                }
+            }
 
-            if head_ref
-                .id()
-                .shorten_or_id()
-                .cmp_oid(intended_commit.as_oid())
-                .is_ne()
-            {
+            // a submodule at another commit would count as a local change, so this goes first
+            if let Some(submodule) = find_outdated_submodule(&repo)? {
                 return Ok(HaxelibStatus::new(
                     haxelib,
-                    InstallType::Outdated,
+                    InstallType::OutdatedSubmodule,
                     get_wants(haxelib),
-                    Some(head_ref.id().to_string()),
+                    Some(submodule),
                 ));
             }
 
             if repo.is_dirty()? {
                 return Ok(HaxelibStatus::new(
                     haxelib,
-                    InstallType::Conflict,
+                    InstallType::Dirty,
                     get_wants(haxelib),
                     None,
                 ));
             }
 
             // we have a correct version, so we're going to update the current_version to to the vcs_ref
-            current_version = haxelib.vcs_ref.as_ref().unwrap().to_string();
+            current_version = match haxelib.vcs_ref.as_ref() {
+                Some(vcs_ref) => vcs_ref.to_string(),
+                None => {
+                    return Ok(HaxelibStatus::new(
+                        haxelib,
+                        InstallType::NotLocked,
+                        None,
+                        Some(head_commit.to_string()),
+                    ))
+                }
+            };
+        }
+        HaxelibType::Dev => {
+            // the .dev file points at the library folder, which needs a haxelib.json to be usable
+            let installed_path = PathBuf::from(current_version.trim());
+            if !lib_path.join(".dev").exists() || !installed_path.join("haxelib.json").exists() {
+                return Ok(HaxelibStatus::new(
+                    haxelib,
+                    InstallType::Missing,
+                    get_wants(haxelib),
+                    None,
+                ));
+            }
+
+            if haxelib.dev_path(json_path) != Some(installed_path) {
+                return Ok(HaxelibStatus::new(
+                    haxelib,
+                    InstallType::Outdated,
+                    get_wants(haxelib),
+                    Some(current_version.to_string()),
+                ));
+            }
         }
         _ => {}
     }
@@ -206,6 +411,37 @@ fn check_dependency(haxelib: &Haxelib) -> Result<HaxelibStatus> {
     ))
 }
 
+/// Describes the first submodule, looking through nested ones too,
+/// that isn't cloned or isn't checked out at the commit its parent records
+fn find_outdated_submodule(repo: &gix::Repository) -> Result<Option<String>> {
+    let submodules = match repo.submodules()? {
+        Some(submodules) => submodules,
+        None => return Ok(None),
+    };
+
+    for submodule in submodules {
+        let commit = match submodule.index_id()? {
+            Some(commit) => commit,
+            None => continue,
+        };
+        let path = submodule.path()?.to_string();
+        let submodule_repo = match submodule.open()? {
+            Some(submodule_repo) => submodule_repo,
+            None => return Ok(Some(format!("{} (not cloned)", path))),
+        };
+
+        match submodule_repo.head_id() {
+            Ok(head) if head == commit => {}
+            Ok(head) => return Ok(Some(format!("{} at {}", path, head))),
+            Err(_) => return Ok(Some(format!("{} (nothing checked out)", path))),
+        }
+        if let Some(nested) = find_outdated_submodule(&submodule_repo)? {
+            return Ok(Some(format!("{}/{}", path, nested)));
+        }
+    }
+    Ok(None)
+}
+
 fn print_install_status(haxelib_status: &HaxelibStatus) -> Result<()> {
     // Clears the terminal
     print!("\x1B[1A\x1B[2K");
@@ -218,7 +454,7 @@ fn print_install_status(haxelib_status: &HaxelibStatus) -> Result<()> {
             );
             println!(
                 "Expected: {} | Installed: {}",
-                haxelib_status.wants.as_ref().unwrap().red(),
+                haxelib_status.wants.as_deref().unwrap_or("None").red(),
                 "None".red()
             );
         }
@@ -230,7 +466,7 @@ fn print_install_status(haxelib_status: &HaxelibStatus) -> Result<()> {
             );
             println!(
                 "Expected: {} | Installed: {}",
-                haxelib_status.wants.as_ref().unwrap().red(),
+                haxelib_status.wants.as_deref().unwrap_or("None").red(),
                 "None".red()
             );
         }
@@ -242,7 +478,7 @@ fn print_install_status(haxelib_status: &HaxelibStatus) -> Result<()> {
             );
             println!(
                 "Expected: {} | Installed: {}",
-                haxelib_status.wants.as_ref().unwrap().red(),
+                haxelib_status.wants.as_deref().unwrap_or("None").red(),
                 haxelib_status.installed.as_ref().unwrap().red()
             );
         }
@@ -256,13 +492,24 @@ fn print_install_status(haxelib_status: &HaxelibStatus) -> Result<()> {
             );
             println!("{}", inner.bright_green().wrap());
         }
-        InstallType::Conflict => {
+        InstallType::Dirty => {
             println!(
                 "{} {}",
                 haxelib_status.lib.name.red().bold(),
                 "has local changes".red()
             );
         }
+        InstallType::OutdatedSubmodule => {
+            println!(
+                "{} {}",
+                haxelib_status.lib.name.red().bold(),
+                "has a submodule that isn't at the commit it records".red()
+            );
+            println!(
+                "Submodule: {}",
+                haxelib_status.installed.as_ref().unwrap().red()
+            );
+        }
         InstallType::NotLocked => {
             println!(
                 "{} {}",
@@ -279,11 +526,12 @@ fn print_install_status(haxelib_status: &HaxelibStatus) -> Result<()> {
     Ok(())
 }
 
-/// Returns either the haxelib version or the git ref of the haxelib
+/// Returns either the haxelib version, the git ref, or the dev dir of the haxelib
 fn get_wants(haxelib: &Haxelib) -> Option<String> {
     match haxelib.haxelib_type {
         HaxelibType::Haxelib => haxelib.version.clone(),
         HaxelibType::Git => haxelib.vcs_ref.clone(),
+        HaxelibType::Dev => haxelib.dir.clone(),
         _ => None,
     }
 }
@@ -301,6 +549,8 @@ mod tests {
             dir: None,
             url: None,
             version: Some("1.0.0".to_string()),
+            overrides: false,
+            shallow: None,
         };
         assert_eq!(get_wants(&haxelib), Some("1.0.0".to_string()));
 
@@ -311,7 +561,53 @@ mod tests {
             dir: None,
             url: None,
             version: None,
+            overrides: false,
+            shallow: None,
         };
         assert_eq!(get_wants(&haxelib), Some("master".to_string()));
+
+        let haxelib = Haxelib {
+            name: "test".to_string(),
+            haxelib_type: HaxelibType::Dev,
+            vcs_ref: None,
+            dir: Some("../test".to_string()),
+            url: None,
+            version: None,
+            overrides: false,
+            shallow: None,
+        };
+        assert_eq!(get_wants(&haxelib), Some("../test".to_string()));
+    }
+
+    #[test]
+    fn test_status_report_json() {
+        let haxelib = Haxelib {
+            name: "lime".to_string(),
+            haxelib_type: HaxelibType::Haxelib,
+            vcs_ref: None,
+            dir: None,
+            url: None,
+            version: Some("8.1.0".to_string()),
+            overrides: false,
+            shallow: None,
+        };
+        let status = HaxelibStatus::new(
+            &haxelib,
+            InstallType::AlreadyInstalled,
+            Some("8.1.0".to_string()),
+            None,
+        );
+
+        let json = serde_json::to_value(StatusReport::from(&status)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "name": "lime",
+                "type": "haxelib",
+                "install_type": "already_installed",
+                "wants": "8.1.0",
+                "installed": "8.1.0"
+            })
+        );
     }
 }
@@ -0,0 +1,58 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use yansi::Paint;
+
+use crate::{
+    commands,
+    hmm::{
+        self,
+        dependencies::Dependancies,
+        haxelib::{Haxelib, HaxelibType},
+    },
+};
+
+pub fn install_dev(
+    name: &str,
+    path: &Path,
+    mut deps: Dependancies,
+    json_path: PathBuf,
+) -> Result<()> {
+    let haxelib_install = Haxelib {
+        name: name.to_string(),
+        haxelib_type: HaxelibType::Dev,
+        vcs_ref: None,
+        dir: Some(dir_for_json(path, &json_path)?),
+        url: None,
+        version: None,
+        overrides: false,
+        shallow: None,
+    };
+
+    commands::install_command::install_from_dev(&haxelib_install, &json_path)?;
+
+    if let Some(previous) = deps.upsert_haxelib(haxelib_install) {
+        println!(
+            "{} [{:?}] replaced with the dev library",
+            previous.name.bold(),
+            previous.haxelib_type
+        );
+    }
+
+    hmm::json::save_json(deps, json_path)
+}
+
+/// The `dir` is resolved against the folder hmm.json is in, so if hmm.json lives
+/// somewhere other than the current directory we save an absolute path instead
+fn dir_for_json(path: &Path, json_path: &Path) -> Result<String> {
+    let json_dir = json_path.parent().unwrap_or(Path::new(""));
+    let json_in_current_dir = json_dir.as_os_str().is_empty()
+        || std::path::absolute(json_dir)? == std::env::current_dir()?;
+
+    let dir = match path.is_relative() && !json_in_current_dir {
+        true => std::path::absolute(path)?,
+        false => path.to_path_buf(),
+    };
+
+    Ok(dir.display().to_string())
+}
@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use yansi::Paint;
+
+use crate::hmm::{self, dependencies::Dependancies, haxelib::HaxelibType, hxml::HxmlLibs};
+
+pub fn import_from_hxml(hxml_paths: &[PathBuf], json_path: PathBuf) -> Result<()> {
+    let mut hxml_libs = HxmlLibs::default();
+    for hxml_path in hxml_paths {
+        hxml_libs.parse_file(hxml_path)?;
+    }
+
+    for (name, kept, ignored) in hxml_libs.conflicts.iter() {
+        println!(
+            "{}: found both {} and {}, keeping {}",
+            name.yellow().bold(),
+            kept,
+            ignored,
+            kept.bold()
+        );
+    }
+
+    let mut deps = match json_path.exists() {
+        true => hmm::json::read_json(&json_path)?,
+        false => Dependancies {
+            dependencies: vec![],
+            registries: vec![],
+        },
+    };
+
+    if hxml_libs.split_builds {
+        println!(
+            "{}",
+            "--next/--each split the hxml into several builds, the libraries of all of them were imported together".yellow()
+        );
+    }
+
+    let mut unpinned = Vec::new();
+    for lib in hxml_libs.libs {
+        let pinned = match lib.haxelib_type {
+            HaxelibType::Git => lib.vcs_ref.is_some(),
+            _ => lib.version.is_some(),
+        };
+
+        // a bare `-lib name` shouldn't wipe out a version that's already in hmm.json
+        if !pinned {
+            if deps.get_haxelib(&lib.name).is_ok() {
+                continue;
+            }
+            unpinned.push(lib.clone());
+        }
+
+        println!("{} imported", lib.name.bold());
+        deps.upsert_haxelib(lib);
+    }
+
+    for lib in unpinned.iter() {
+        let reason = match lib.haxelib_type {
+            HaxelibType::Git => {
+                "has no ref in the hxml, so it was added on the remote's default branch"
+            }
+            _ => "has no version in the hxml, so it was added with `\"version\": null`",
+        };
+        println!("{} {}", lib.name.yellow().bold(), reason.yellow());
+    }
+    if !unpinned.is_empty() {
+        println!(
+            "{}",
+            "Install them and run `hmm-rs lock` to pin them to a version".yellow()
+        );
+    }
+
+    hmm::json::save_json(deps, json_path)
+}
@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use yansi::Paint;
+
+use crate::{
+    commands::install_command,
+    hmm::{
+        self,
+        dependencies::Dependancies,
+        haxelib::{Haxelib, HaxelibType},
+    },
+};
+
+pub fn install_git(
+    name: &str,
+    url: &str,
+    vcs_ref: &Option<String>,
+    lock: bool,
+    mut deps: Dependancies,
+    json_path: PathBuf,
+) -> Result<()> {
+    let mut haxelib_install = Haxelib {
+        name: name.to_string(),
+        haxelib_type: HaxelibType::Git,
+        vcs_ref: vcs_ref.clone(),
+        dir: None,
+        url: Some(url.to_string()),
+        version: None,
+        overrides: false,
+        // re-pinning shouldn't drop a clone mode the user picked
+        shallow: deps
+            .get_haxelib(name)
+            .ok()
+            .and_then(|existing| existing.shallow),
+    };
+
+    // local paths get made absolute when cloning, so the comparison has to see them the same way
+    let clone_url = install_command::clone_url(&haxelib_install)?
+        .to_bstring()
+        .to_string();
+    let repo_path = haxelib_install.lib_path().join("git");
+    match gix::open(&repo_path) {
+        Ok(repo) if remote_url(&repo).as_ref() == Some(&clone_url) => {
+            install_command::install_from_git_using_gix_checkout(&haxelib_install)?
+        }
+        Ok(_) => {
+            // it's a clone of a different repo, so start over from scratch
+            std::fs::remove_dir_all(&repo_path)?;
+            install_command::install_from_git_using_gix_clone(&haxelib_install)?
+        }
+        Err(_) => install_command::install_from_git_using_gix_clone(&haxelib_install)?,
+    }
+
+    let repo = gix::open(&repo_path)?;
+    if lock {
+        haxelib_install.vcs_ref = Some(repo.head_id()?.to_string());
+    } else if haxelib_install.vcs_ref.is_none() {
+        // no ref was given, so we use whatever the remote's default branch is
+        let head_name = repo
+            .head_name()?
+            .ok_or(anyhow!("{} has no default branch", url))?;
+        haxelib_install.vcs_ref = Some(head_name.shorten().to_string());
+    }
+
+    println!(
+        "{}: {} installed from git",
+        haxelib_install.name.green().bold(),
+        haxelib_install.vcs_ref.as_ref().unwrap().bright_green()
+    );
+
+    if let Some(previous) = deps.upsert_haxelib(haxelib_install) {
+        println!(
+            "{} [{:?}] in hmm.json was replaced",
+            previous.name.bold(),
+            previous.haxelib_type
+        );
+    }
+
+    hmm::json::save_json(deps, json_path)
+}
+
+fn remote_url(repo: &gix::Repository) -> Option<String> {
+    let remote = repo
+        .find_default_remote(gix::remote::Direction::Fetch)?
+        .ok()?;
+    let url = remote.url(gix::remote::Direction::Fetch)?;
+    Some(url.to_bstring().to_string())
+}
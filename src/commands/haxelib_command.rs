@@ -1,20 +1,27 @@
 use std::path::PathBuf;
 
-use anyhow::{anyhow, Ok, Result};
-use reqwest::blocking::Client;
+use anyhow::{anyhow, Result};
+use yansi::Paint;
 
 use crate::{
-    commands,
+    commands::{
+        self,
+        check_command::{self, InstallType},
+    },
     hmm::{
         self,
+        cache::Cache,
+        config,
         dependencies::Dependancies,
         haxelib::{Haxelib, HaxelibType},
+        registry,
     },
 };
 
 pub fn install_haxelib(
     name: &str,
     version: &Option<String>,
+    force: bool,
     mut deps: Dependancies,
     json_path: PathBuf,
 ) -> Result<()> {
@@ -25,42 +32,106 @@ pub fn install_haxelib(
         dir: None,
         url: None,
         version: None,
+        overrides: false,
+        shallow: None,
     };
     match version {
         Some(version) => haxelib_install.version = Some(version.to_string()),
 
         None => {
-            // we need to query the latest version from haxelib
-            // haxelib url: lib.haxe.org/api/3.0/index.n/
-            // needs X-Haxe-Remoting header
-            // and __x param with the query
-            // in __x param, we can query with something like
-            // ay3:apiy16:getLatestVersionhay4:limeh
+            let latest_version = get_latest_version(name)?;
+            println!("Latest version of {} is {}", name, latest_version);
+            haxelib_install.version = Some(latest_version);
+        }
+    };
 
-            let serialized = format!("ay3:apiy16:getLatestVersionhay{}:{}h", name.len(), name);
-            let client = Client::new();
+    if let Ok(existing) = deps.get_haxelib(name) {
+        // changing the version shouldn't drop an override the user already set
+        haxelib_install.overrides = existing.overrides;
+        if existing.haxelib_type != HaxelibType::Haxelib && !force {
+            let prompt = format!(
+                "{} is currently a {:?} dependency, replace it with the haxelib version?",
+                name, existing.haxelib_type
+            );
+            if !confirm(&prompt)? {
+                return Err(anyhow!(
+                    "{} was not replaced, use `--force` to replace it anyways",
+                    name
+                ));
+            }
+        }
+    }
 
-            let resp = client
-                .get("https://lib.haxe.org/api/3.0/index.n/")
-                .header("X-Haxe-Remoting", "1")
-                .query(&[("__x", serialized)])
-                .send()?;
+    let install_status = check_command::check_haxelib(&haxelib_install, &json_path)?;
+    if install_status.install_type != InstallType::AlreadyInstalled {
+        commands::install_command::install_from_haxelib(&haxelib_install)?;
+    }
 
-            let resp = resp.text()?;
-            let resp_splits = resp.split(":").collect::<Vec<&str>>();
-            let decoded_resp = urlencoding::decode(resp_splits[1])?;
+    let new_version = haxelib_install.version.clone().unwrap();
+    match deps.upsert_haxelib(haxelib_install) {
+        Some(previous) => {
+            let previous_version = match previous.haxelib_type {
+                HaxelibType::Haxelib => previous.version,
+                HaxelibType::Git => previous.vcs_ref,
+                _ => previous.dir,
+            };
+            println!(
+                "{}: {} -> {}",
+                name.bold(),
+                previous_version.unwrap_or_else(|| "null".to_string()).red(),
+                new_version.green()
+            );
+        }
+        None => println!("{}: {} added", name.bold(), new_version.green()),
+    }
 
-            println!("Latest version of {} is {}", name, decoded_resp);
+    hmm::json::save_json(deps, json_path)?;
+    Ok(())
+}
 
-            if decoded_resp.starts_with("No such Project") {
-                return Err(anyhow!("{}", decoded_resp)); // this haxelib doesn't exist
+/// Asks each registry in turn for the latest version of a haxelib,
+/// falling back to the download cache when offline
+pub fn get_latest_version(name: &str) -> Result<String> {
+    let mut last_error = anyhow!("No registries to ask for {}", name);
+    for registry in registry::registries() {
+        match registry.latest_version(name) {
+            Ok(version) => return Ok(version),
+            Err(e) => {
+                last_error = e.context(format!("Error asking {} about {}", registry.name(), name))
             }
-
-            haxelib_install.version = Some(decoded_resp.to_string());
         }
+    }
+
+    match config::settings().offline {
+        true => newest_cached_version(name),
+        false => Err(last_error),
+    }
+}
+
+fn newest_cached_version(name: &str) -> Result<String> {
+    let zips = match Cache::enabled()? {
+        Some(cache) => cache.list_zips()?,
+        None => vec![],
     };
-    commands::install_command::install_from_haxelib(&haxelib_install)?;
-    deps.dependencies.push(haxelib_install);
-    hmm::json::save_json(deps, json_path)?;
-    Ok(())
+
+    zips.into_iter()
+        .filter(|zip| zip.name == name)
+        .map(|zip| zip.version)
+        .max_by_key(|version| semver::Version::parse(version).ok())
+        .ok_or(anyhow!(
+            "Offline, and there's no version of {} in the cache",
+            name
+        ))
+}
+
+/// Asks a yes / no question, defaulting to no when there's nobody around to answer
+fn confirm(prompt: &str) -> Result<bool> {
+    if !console::user_attended() {
+        return Ok(false);
+    }
+
+    print!("{} [y/N] ", prompt.yellow());
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let answer = console::Term::stdout().read_line()?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
 }
@@ -1,69 +1,539 @@
 use crate::commands::check_command::InstallType;
+use crate::hmm::cache::{sha256_file, Cache};
+use crate::hmm::config;
 use crate::hmm::dependencies::Dependancies;
 use crate::hmm::haxelib::Haxelib;
 use crate::hmm::haxelib::HaxelibType;
+use crate::hmm::lockfile::{self, DownloadRecord, LockedDependency, Lockfile};
+use crate::hmm::registry;
+use crate::hmm::resolver::{self, DependencyGraph, TransitiveDependency};
 use anyhow::Ok;
 use anyhow::{anyhow, Context, Result};
-use bstr::BString;
+use bstr::{BStr, BString};
 use console::Emoji;
-use futures_util::StreamExt;
 use gix::clone;
+use gix::config::tree::gitoxide::Committer;
 use gix::create;
 use gix::progress::Discard;
-use gix::Url;
+use gix::refs::transaction::{Change, LogChange, PreviousValue, RefEdit, RefLog};
+use gix::refs::Target;
+use gix::refspec::parse::Operation;
+use gix::remote::fetch::{Shallow, Status, Tags};
+use gix::remote::Direction;
+use gix::{ObjectId, Url};
 use human_bytes::human_bytes;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::env;
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
-use std::path::PathBuf;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicBool;
 use yansi::Paint;
 use zip::ZipArchive;
 
-use super::check_command::compare_haxelib_to_hmm;
-use super::check_command::HaxelibStatus;
+use super::check_command::{check_dependency, check_haxelib, HaxelibStatus};
+use super::check_command::{compare_haxelib_to_hmm, describe_conflict};
+use super::haxelib_command::get_latest_version;
 
-pub fn install_from_hmm(deps: &Dependancies) -> Result<()> {
-    let installs_needed = compare_haxelib_to_hmm(deps)?;
+/// Installs everything in hmm.json, and whatever those libraries require.
+/// `frozen` errors instead of installing anything that isn't pinned to an exact version,
+/// or that would change hmm-lock.json
+pub fn install_from_hmm(deps: &Dependancies, json_path: &Path, frozen: bool) -> Result<()> {
+    let previous_lockfile = match frozen {
+        true => {
+            ensure_pinned(deps)?;
+            let path = lockfile::lockfile_path(json_path);
+            if !path.exists() {
+                return Err(anyhow!(
+                    "`--frozen` installs what {} has locked, but it doesn't exist\nRun `hmm-rs install` without `--frozen` to create it",
+                    path.display()
+                ));
+            }
+            let previous_lockfile = Lockfile::read(&path)?;
+            // the first difference aborts, before anything gets downloaded or checked out
+            for haxelib in deps.dependencies.iter() {
+                ensure_matches_lockfile(haxelib, &previous_lockfile)?;
+            }
+            Some(previous_lockfile)
+        }
+        false => None,
+    };
+
+    // conflicts between hmm.json and what's already installed get refused before .haxelib/ changes.
+    // Transitive dependencies get checked again as they're installed
+    ensure_no_conflicts(&resolver::resolve(deps, json_path)?)?;
+
+    let installs_needed = compare_haxelib_to_hmm(deps, json_path)?;
     println!(
         "{} dependencies need to be installed",
         installs_needed.len().to_string().bold()
     );
 
+    ensure_cached(installs_needed.iter().filter_map(|status| {
+        match (&status.install_type, &status.lib.haxelib_type) {
+            (InstallType::Missing | InstallType::MissingGit, _) => Some(status.lib),
+            // outdated git dependencies get checked out from the clone they already have
+            (InstallType::Outdated, HaxelibType::Haxelib) => Some(status.lib),
+            _ => None,
+        }
+    }))?;
+
     for install_status in installs_needed.iter() {
         match &install_status.install_type {
-            InstallType::Missing => handle_install(install_status)?,
+            InstallType::Missing | InstallType::MissingGit => {
+                handle_install(install_status, json_path)?
+            }
             InstallType::Outdated => match &install_status.lib.haxelib_type {
                 HaxelibType::Haxelib => install_from_haxelib(install_status.lib)?,
                 HaxelibType::Git => install_from_git_using_gix_checkout(install_status.lib)?,
-                lib_type => println!(
-                    "{}: Installing from {:?} not yet implemented",
-                    install_status.lib.name.red(),
-                    lib_type
-                ),
+                HaxelibType::Dev => install_from_dev(install_status.lib, json_path)?,
+                lib_type => return Err(unsupported_type(&install_status.lib.name, lib_type)),
             },
+            InstallType::OutdatedSubmodule => {
+                install_from_git_using_gix_checkout(install_status.lib)?
+            }
             InstallType::AlreadyInstalled => (), // do nothing on things already installed at the right version
-            _ => println!(
-                "{} {:?}: Not implemented",
-                install_status.lib.name, install_status.install_type
-            ),
+            // installed, just not at a version hmm.json pins, frozen installs already errored on it
+            InstallType::NotLocked => (),
+            InstallType::Dirty => {
+                return Err(anyhow!(
+                    "{} has local changes, commit or discard them, or run `hmm-rs reinstall {}`",
+                    install_status.lib.name,
+                    install_status.lib.name
+                ))
+            }
+        }
+    }
+
+    // conflicts get checked in here, once everything in hmm.json has its haxelib.json installed
+    install_transitive_dependencies(deps, json_path, previous_lockfile.as_ref())?;
+
+    let lockfile = Lockfile::from_installed(deps, json_path)?;
+    if let Some(previous_lockfile) = previous_lockfile {
+        return ensure_lockfile_unchanged(&previous_lockfile, &lockfile);
+    }
+    lockfile.save(&lockfile::lockfile_path(json_path))
+}
+
+/// Errors on every dependency that isn't pinned to an exact version
+fn ensure_pinned(deps: &Dependancies) -> Result<()> {
+    let mut problems = Vec::new();
+
+    for haxelib in deps.dependencies.iter() {
+        match haxelib.haxelib_type {
+            HaxelibType::Haxelib if haxelib.version.is_none() => {
+                problems.push(format!("{} has `\"version\": null`", haxelib.name))
+            }
+            HaxelibType::Git => match haxelib.vcs_ref.as_ref() {
+                None => problems.push(format!("{} has no ref", haxelib.name)),
+                Some(vcs_ref) => {
+                    if is_git_branch(haxelib, vcs_ref)? {
+                        problems.push(format!(
+                            "{} is on the branch {}, instead of a commit or tag",
+                            haxelib.name, vcs_ref
+                        ))
+                    }
+                }
+            },
+            _ => (),
+        }
+    }
+
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Dependencies aren't pinned to exact versions:\n  {}\nRun `hmm-rs lock` to pin them",
+            problems.join("\n  ")
+        )),
+    }
+}
+
+/// Whether `vcs_ref` is a branch, without cloning the repo just to find out
+fn is_git_branch(haxelib: &Haxelib, vcs_ref: &str) -> Result<bool> {
+    Ok(matches!(
+        resolve_ref_without_cloning(haxelib, vcs_ref)?,
+        Some(ResolvedRef::Branch { .. })
+    ))
+}
+
+/// Resolves `vcs_ref`, asking the local clone or the cached repo when they know the ref.
+/// Otherwise the remote's refs get listed, which is a lot less than cloning it.
+/// `None` is an abbreviated commit, which only a clone could expand
+fn resolve_ref_without_cloning(haxelib: &Haxelib, vcs_ref: &str) -> Result<Option<ResolvedRef>> {
+    // a full commit SHA can't be anything else, and doesn't need the network
+    if vcs_ref.len() == 40 && looks_like_sha(vcs_ref) {
+        return Ok(Some(ResolvedRef::Detached(ObjectId::from_hex(
+            vcs_ref.as_bytes(),
+        )?)));
+    }
+
+    if let core::result::Result::Ok(repo) = gix::open(haxelib.lib_path().join("git")) {
+        if let core::result::Result::Ok(resolved) = resolve_ref(&repo, vcs_ref) {
+            return Ok(Some(resolved));
+        }
+    }
+
+    let clone_url = clone_url(haxelib)?;
+    if let Some(cache) = Cache::enabled()? {
+        let cached_path = cache.repo_path(&clone_url.to_bstring().to_string());
+        if let core::result::Result::Ok(repo) = gix::open(cached_path) {
+            // the cached repo mirrors the remote's branches as its own
+            if let core::result::Result::Ok(resolved) = resolve_ref(&repo, vcs_ref) {
+                return Ok(Some(resolved));
+            }
+        }
+    }
+
+    let remote_refs =
+        list_refs_at_url(clone_url).context(format!("Couldn't look up {}", vcs_ref))?;
+    let branch = format!("refs/heads/{}", vcs_ref);
+    if let Some(commit) = remote_refs.get(&branch) {
+        return Ok(Some(ResolvedRef::Branch {
+            name: branch,
+            commit: *commit,
+        }));
+    }
+    match remote_refs.get(&format!("refs/tags/{}", vcs_ref)) {
+        Some(commit) => Ok(Some(ResolvedRef::Detached(*commit))),
+        None => Ok(None),
+    }
+}
+
+/// Errors when installing `haxelib` would put something in hmm-lock.json other than what's locked there
+fn ensure_matches_lockfile(haxelib: &Haxelib, lockfile: &Lockfile) -> Result<()> {
+    let locked = lockfile
+        .dependencies
+        .iter()
+        .find(|locked| locked.name == haxelib.name && locked.haxelib_type == haxelib.haxelib_type)
+        .ok_or_else(|| {
+            anyhow!(
+                "{} isn't in hmm-lock.json\nRun `hmm-rs install` without `--frozen` to add it",
+                haxelib.name
+            )
+        })?;
+
+    let (wants, matches) = match haxelib.haxelib_type {
+        HaxelibType::Git => {
+            let vcs_ref = haxelib.vcs_ref.as_deref().unwrap_or_default();
+            let wants = match resolve_ref_without_cloning(haxelib, vcs_ref)? {
+                Some(resolved) => resolved.commit().to_string(),
+                None => vcs_ref.to_string(),
+            };
+            let matches = locked.url == haxelib.url
+                && locked
+                    .commit
+                    .as_deref()
+                    .is_some_and(|commit| commit.starts_with(&wants));
+            (wants, matches)
+        }
+        HaxelibType::Dev => (
+            haxelib.dir.clone().unwrap_or_default(),
+            locked.dir == haxelib.dir,
+        ),
+        _ => (
+            haxelib.version.clone().unwrap_or_default(),
+            locked.version == haxelib.version,
+        ),
+    };
+
+    match matches {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "{} is locked at {} in hmm-lock.json, but would be installed at {}\nRun `hmm-rs install` without `--frozen` to update it",
+            haxelib.name,
+            describe_locked(locked),
+            wants
+        )),
+    }
+}
+
+fn describe_locked(locked: &LockedDependency) -> &str {
+    locked
+        .commit
+        .as_deref()
+        .or(locked.version.as_deref())
+        .or(locked.dir.as_deref())
+        .unwrap_or("None")
+}
+
+fn ensure_lockfile_unchanged(previous: &Lockfile, current: &Lockfile) -> Result<()> {
+    let mut changed: Vec<&str> = current
+        .dependencies
+        .iter()
+        .filter(|locked| !previous.dependencies.contains(locked))
+        .map(|locked| locked.name.as_str())
+        .collect();
+    changed.extend(
+        previous
+            .dependencies
+            .iter()
+            .filter(|locked| !current.dependencies.iter().any(|c| c.name == locked.name))
+            .map(|locked| locked.name.as_str()),
+    );
+
+    match changed.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "hmm-lock.json would change for: {}\nRun `hmm-rs install` without `--frozen` to update it",
+            changed.join(", ")
+        )),
+    }
+}
+
+pub fn unsupported_type(name: &str, lib_type: &HaxelibType) -> anyhow::Error {
+    anyhow!(
+        "{}: Installing from {:?} not yet implemented",
+        name,
+        lib_type
+    )
+}
+
+/// Installs exactly what hmm-lock.json lists, instead of resolving hmm.json again
+pub fn install_from_lockfile(json_path: &Path) -> Result<()> {
+    let lockfile_path = lockfile::lockfile_path(json_path);
+    let lockfile = Lockfile::read(&lockfile_path)?;
+    println!(
+        "Installing {} dependencies from {}",
+        lockfile.dependencies.len().to_string().bold(),
+        lockfile_path.display()
+    );
+
+    let haxelibs: Vec<Haxelib> = lockfile
+        .dependencies
+        .iter()
+        .map(|l| l.to_haxelib())
+        .collect();
+    ensure_cached(haxelibs.iter().filter(|haxelib| {
+        check_dependency(haxelib, json_path)
+            .is_ok_and(|status| status.install_type != InstallType::AlreadyInstalled)
+    }))?;
+
+    for (locked, haxelib) in lockfile.dependencies.iter().zip(haxelibs.iter()) {
+        let install_status = check_haxelib(haxelib, json_path)?;
+
+        match install_status.install_type {
+            InstallType::AlreadyInstalled => continue,
+            InstallType::Missing | InstallType::MissingGit => {
+                handle_install(&install_status, json_path)?
+            }
+            InstallType::Outdated | InstallType::OutdatedSubmodule => match haxelib.haxelib_type {
+                HaxelibType::Git => install_from_git_using_gix_checkout(haxelib)?,
+                _ => handle_install(&install_status, json_path)?,
+            },
+            _ => {
+                return Err(anyhow!(
+                    "{} {:?}: can't be installed from {}",
+                    haxelib.name,
+                    install_status.install_type,
+                    lockfile_path.display()
+                ))
+            }
+        }
+
+        if haxelib.haxelib_type == HaxelibType::Haxelib {
+            verify_download(locked)?;
         }
     }
 
     Ok(())
 }
 
-pub fn handle_install(haxelib_status: &HaxelibStatus) -> Result<()> {
+/// Makes sure the haxelib zip that just got installed is the same one the lockfile recorded
+fn verify_download(locked: &LockedDependency) -> Result<()> {
+    let expected = match &locked.sha256 {
+        Some(expected) => expected,
+        None => return Ok(()),
+    };
+
+    let version = locked.version.as_deref().unwrap_or_default();
+    let version_dir = locked
+        .to_haxelib()
+        .lib_path()
+        .join(version.replace(".", ","));
+    let downloaded = DownloadRecord::read(&version_dir).map(|record| record.sha256);
+    if downloaded.as_ref() == Some(expected) {
+        return Ok(());
+    }
+
+    // don't leave the mismatched files around for the next install to think they're fine
+    std::fs::remove_dir_all(&version_dir)?;
+    Err(anyhow!(
+        "{} {} doesn't match hmm-lock.json\nExpected sha256: {}\nDownloaded sha256: {}",
+        locked.name,
+        version,
+        expected,
+        downloaded.unwrap_or_else(|| "None".to_string())
+    ))
+}
+
+/// Installs libraries that aren't in hmm.json, but are required by the haxelib.json of something that is.
+/// With `frozen_lockfile`, each one has to match what's locked before it gets installed
+fn install_transitive_dependencies(
+    deps: &Dependancies,
+    json_path: &Path,
+    frozen_lockfile: Option<&Lockfile>,
+) -> Result<()> {
+    let mut attempted = HashSet::new();
+
+    // newly installed libraries can require even more libraries, so keep going until nothing is missing
+    loop {
+        let graph = resolver::resolve(deps, json_path)?;
+        ensure_no_conflicts(&graph)?;
+
+        let missing: Vec<&TransitiveDependency> = graph.missing().collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        ensure_cached(missing.iter().map(|transitive| &transitive.haxelib))?;
+
+        for transitive in missing {
+            let mut haxelib = transitive.haxelib.clone();
+            if !attempted.insert(haxelib.name.clone()) {
+                return Err(anyhow!(
+                    "{} was installed, but still can't be found",
+                    haxelib.name
+                ));
+            }
+
+            let required_by: Vec<&str> = transitive
+                .required_by
+                .iter()
+                .map(|r| r.required_by.as_str())
+                .collect();
+            println!(
+                "{} is required by {}",
+                haxelib.name.bold(),
+                required_by.join(", ").bold()
+            );
+
+            if let Some(lockfile) = frozen_lockfile {
+                // "any version" installs whatever was locked, rather than the latest
+                if haxelib.version.is_none() {
+                    haxelib.version = lockfile
+                        .dependencies
+                        .iter()
+                        .find(|locked| locked.name == haxelib.name)
+                        .and_then(|locked| locked.version.clone());
+                }
+                ensure_matches_lockfile(&haxelib, lockfile)?;
+            }
+
+            match haxelib.haxelib_type {
+                HaxelibType::Git => install_from_git_using_gix_clone(&haxelib)?,
+                _ => {
+                    if haxelib.version.is_none() {
+                        haxelib.version = Some(get_latest_version(&haxelib.name)?);
+                    }
+                    install_from_haxelib(&haxelib)?
+                }
+            }
+        }
+    }
+}
+
+/// When offline, errors with everything in `haxelibs` that can't be installed from the caches
+pub fn ensure_cached<'a>(haxelibs: impl Iterator<Item = &'a Haxelib>) -> Result<()> {
+    if !config::settings().offline {
+        return Ok(());
+    }
+
+    let cache = Cache::enabled()?;
+    let missing: Vec<String> = haxelibs
+        .filter(|haxelib| !is_cached(cache.as_ref(), haxelib))
+        .map(|haxelib| match haxelib.haxelib_type {
+            HaxelibType::Git => format!(
+                "{} {}#{}",
+                haxelib.name,
+                haxelib.url.as_deref().unwrap_or("None"),
+                haxelib.vcs_ref.as_deref().unwrap_or("HEAD")
+            ),
+            _ => format!(
+                "{} {}",
+                haxelib.name,
+                haxelib.version.as_deref().unwrap_or("(any version)")
+            ),
+        })
+        .collect();
+
+    match missing.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Offline, and these aren't in the cache:\n  {}",
+            missing.join("\n  ")
+        )),
+    }
+}
+
+fn is_cached(cache: Option<&Cache>, haxelib: &Haxelib) -> bool {
+    // a folder registry works offline just like the cache does
+    if haxelib.haxelib_type == HaxelibType::Haxelib && in_local_registry(haxelib) {
+        return true;
+    }
+
+    let cache = match cache {
+        Some(cache) => cache,
+        None => return false,
+    };
+
+    match haxelib.haxelib_type {
+        HaxelibType::Haxelib => match haxelib.version.as_ref() {
+            Some(version) => cache.find_zip(&haxelib.name, version).is_some(),
+            None => cache
+                .list_zips()
+                .is_ok_and(|zips| zips.iter().any(|zip| zip.name == haxelib.name)),
+        },
+        HaxelibType::Git => {
+            let repo = clone_url(haxelib)
+                .ok()
+                .map(|url| cache.repo_path(&url.to_bstring().to_string()))
+                .and_then(|repo_path| gix::open(repo_path).ok());
+            match (repo, haxelib.vcs_ref.as_ref()) {
+                (Some(repo), Some(vcs_ref)) => repo.rev_parse_single(vcs_ref.as_str()).is_ok(),
+                (Some(_), None) => true,
+                (None, _) => false,
+            }
+        }
+        // dev dependencies are already on disk, and hg can't be installed anyways
+        _ => true,
+    }
+}
+
+fn in_local_registry(haxelib: &Haxelib) -> bool {
+    registry::registries()
+        .iter()
+        .filter(|registry| registry.is_local())
+        .filter_map(|registry| registry.versions(&haxelib.name).ok())
+        .any(|versions| match haxelib.version.as_ref() {
+            Some(version) => versions.contains(version),
+            None => !versions.is_empty(),
+        })
+}
+
+/// Errors out on version conflicts, unless hmm.json overrides them
+fn ensure_no_conflicts(graph: &DependencyGraph) -> Result<()> {
+    let mut message = String::new();
+    for conflict in graph.unresolved_conflicts() {
+        message.push_str(&format!("\n{} has conflicting versions", conflict.name));
+        for line in describe_conflict(conflict) {
+            message.push_str(&format!("\n  {}", line));
+        }
+    }
+
+    match message.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Version conflicts found:{}\nChange the versions, or add `\"override\": true` to the library in hmm.json to keep its version",
+            message
+        )),
+    }
+}
+
+pub fn handle_install(haxelib_status: &HaxelibStatus, json_path: &Path) -> Result<()> {
     match &haxelib_status.lib.haxelib_type {
         HaxelibType::Haxelib => install_from_haxelib(haxelib_status.lib)?,
         HaxelibType::Git => install_from_git_using_gix_clone(haxelib_status.lib)?,
-        lib_type => println!(
-            "{}: Installing from {:?} not yet implemented",
-            haxelib_status.lib.name.red(),
-            lib_type
-        ),
+        HaxelibType::Dev => install_from_dev(haxelib_status.lib, json_path)?,
+        lib_type => return Err(unsupported_type(&haxelib_status.lib.name, lib_type)),
     }
 
     Ok(())
@@ -72,25 +542,9 @@ pub fn handle_install(haxelib_status: &HaxelibStatus) -> Result<()> {
 pub fn install_from_git_using_gix_clone(haxelib: &Haxelib) -> Result<()> {
     println!("Installing {} from git using clone", haxelib.name);
 
-    let haxelib_url = haxelib
-        .url
-        .as_ref()
-        .ok_or(anyhow!("No url provided for {}", haxelib.name))?;
-
-    let path_with_no_https = haxelib_url.replace("https://", "");
-
-    let clone_url = Url::from_parts(
-        gix::url::Scheme::Https,
-        None,
-        None,
-        None,
-        None,
-        BString::from(path_with_no_https),
-        false,
-    )
-    .context(format!("error creating gix url for {}", haxelib_url))?;
+    let clone_url = clone_url(haxelib)?;
 
-    let mut clone_path = PathBuf::from(".haxelib").join(&haxelib.name);
+    let mut clone_path = haxelib.lib_path();
 
     create_current_file(&clone_path, &String::from("git"))?;
 
@@ -107,6 +561,146 @@ pub fn install_from_git_using_gix_clone(haxelib: &Haxelib) -> Result<()> {
         }
     };
 
+    let repo = clone_dependency(haxelib, clone_url, clone_path)?;
+
+    // checking out writes reflogs, which needs a committer
+    let repo = open_git_repo(repo.path())?;
+    fetch_until_resolvable(&repo, haxelib)?;
+    do_commit_checkout(&repo, haxelib)?;
+    update_submodules(&repo, haxelib)?;
+
+    Ok(())
+}
+
+/// Clones through the cache when it's enabled, shallow if the dependency asks for it
+fn clone_dependency(
+    haxelib: &Haxelib,
+    clone_url: Url,
+    clone_path: PathBuf,
+) -> Result<gix::Repository> {
+    let shallow = is_shallow(haxelib);
+    let cache = Cache::enabled()?;
+    // shallow clones go straight to the remote, unless the cache already has the whole repo
+    let cache = cache.filter(|cache| {
+        !shallow
+            || cache
+                .repo_path(&clone_url.to_bstring().to_string())
+                .exists()
+    });
+
+    match cache {
+        Some(cache) => clone_through_cache(&cache, clone_url, clone_path),
+        None => {
+            config::ensure_online(&format!("clone {}", clone_url.to_bstring()))?;
+            match shallow {
+                true => shallow_clone(clone_url, clone_path, haxelib.vcs_ref.as_deref()),
+                false => clone_repo(clone_url, clone_path),
+            }
+        }
+    }
+}
+
+/// Clones any submodules that aren't there yet and checks each one out at the commit
+/// the parent's tree records, then does the same for their submodules
+fn update_submodules(repo: &gix::Repository, haxelib: &Haxelib) -> Result<()> {
+    let submodules = match repo.submodules()? {
+        Some(submodules) => submodules,
+        None => return Ok(()),
+    };
+
+    for submodule in submodules {
+        // listed in .gitmodules but not in the tree, so there's nothing to check out
+        let commit = match submodule.index_id()? {
+            Some(commit) => commit,
+            None => continue,
+        };
+        let url = submodule_url(repo, submodule.url()?)?;
+        let submodule_lib = Haxelib {
+            name: format!("{}/{}", haxelib.name, submodule.path()?),
+            haxelib_type: HaxelibType::Git,
+            vcs_ref: Some(commit.to_string()),
+            dir: None,
+            url: Some(url.to_bstring().to_string()),
+            version: None,
+            overrides: false,
+            shallow: haxelib.shallow,
+        };
+
+        let submodule_repo = match submodule.open()? {
+            Some(submodule_repo) => submodule_repo,
+            None => {
+                println!(
+                    "Cloning submodule {} from {}",
+                    submodule_lib.name.bold(),
+                    url.to_bstring()
+                );
+                clone_dependency(&submodule_lib, url, submodule.work_dir()?)?
+            }
+        };
+
+        let submodule_repo = open_git_repo(submodule_repo.path())?;
+        if submodule_repo.head_id().ok().map(|id| id.detach()) != Some(commit) {
+            fetch_until_resolvable(&submodule_repo, &submodule_lib)?;
+            do_commit_checkout(&submodule_repo, &submodule_lib)?;
+        }
+        update_submodules(&submodule_repo, &submodule_lib)?;
+    }
+    Ok(())
+}
+
+/// Submodule urls like `../native.git` are relative to the parent repo's remote
+fn submodule_url(repo: &gix::Repository, url: Url) -> Result<Url> {
+    let relative_path = url.path.to_string();
+    if url.scheme != gix::url::Scheme::File
+        || !(relative_path.starts_with("./") || relative_path.starts_with("../"))
+    {
+        return Ok(url);
+    }
+
+    let mut resolved = repo
+        .find_fetch_remote(None)?
+        .url(Direction::Fetch)
+        .ok_or(anyhow!(
+            "{} has no remote to resolve the submodule url {} against",
+            repo.path().display(),
+            relative_path
+        ))?
+        .clone();
+    let mut path = resolved.path.to_string().trim_end_matches('/').to_string();
+    for component in relative_path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => path.truncate(path.rfind('/').unwrap_or(0)),
+            component => {
+                path.push('/');
+                path.push_str(component);
+            }
+        }
+    }
+    resolved.path = path.into();
+    Ok(resolved)
+}
+
+pub fn clone_url(haxelib: &Haxelib) -> Result<Url> {
+    let haxelib_url = haxelib
+        .url
+        .as_ref()
+        .ok_or(anyhow!("No url provided for {}", haxelib.name))?;
+
+    // handles every scheme git does, and scp-like `git@github.com:org/repo.git`
+    let mut clone_url = gix::url::parse(haxelib_url.as_str().into())
+        .context(format!("{} is not a valid git url", haxelib_url))?;
+
+    // local repos get cached by url, so `../repo` in two projects can't be the same key
+    if clone_url.scheme == gix::url::Scheme::File {
+        let path = std::path::absolute(gix::path::from_bstr(BStr::new(&clone_url.path)))?;
+        clone_url.path = gix::path::into_bstr(path).into_owned();
+    }
+
+    Ok(clone_url)
+}
+
+fn clone_repo(clone_url: Url, clone_path: PathBuf) -> Result<gix::Repository> {
     let mut da_fetch = clone::PrepareFetch::new(
         clone_url,
         clone_path,
@@ -117,106 +711,275 @@ pub fn install_from_git_using_gix_clone(haxelib: &Haxelib) -> Result<()> {
     .context("error preparing clone")?;
 
     let repo = da_fetch
-        .fetch_then_checkout(Discard, &AtomicBool::new(false))?
+        .fetch_then_checkout(Discard, &AtomicBool::new(false))
+        .context("error cloning")?
         .0
         .main_worktree(Discard, &AtomicBool::new(false))
-        .expect("Error checking out worktree")
+        .context("error checking out worktree")?
         .0;
 
-    let submodule_result = repo.submodules()?;
+    Ok(repo)
+}
 
-    if let Some(submodule_list) = submodule_result {
-        for submodule in submodule_list {
-            let submodule_path = submodule.path()?;
-            let submodule_url = submodule.url()?;
-            println!("Submodule: {} - {}", submodule_path, submodule_url);
-        }
-    }
+/// Whether to clone a git dependency shallow, hmm.json wins over the global setting
+fn is_shallow(haxelib: &Haxelib) -> bool {
+    haxelib.shallow.unwrap_or(config::settings().shallow)
+}
 
-    do_commit_checkout(&repo, haxelib)?;
+fn looks_like_sha(vcs_ref: &str) -> bool {
+    vcs_ref.len() >= 4 && vcs_ref.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Clones only `vcs_ref` at depth 1, without tags. It might be a branch or a tag so both get asked for,
+/// commits can't be asked for by name so those start from the remote's HEAD and deepen from there
+fn shallow_clone(
+    clone_url: Url,
+    clone_path: PathBuf,
+    vcs_ref: Option<&str>,
+) -> Result<gix::Repository> {
+    let refspecs: Vec<String> = match vcs_ref {
+        Some(vcs_ref) if !looks_like_sha(vcs_ref) => vec![
+            format!("+refs/heads/{0}:refs/remotes/origin/{0}", vcs_ref),
+            format!("+refs/tags/{0}:refs/tags/{0}", vcs_ref),
+        ],
+        // the remote's HEAD always gets fetched by a clone
+        _ => vec![],
+    };
+
+    let mut prepare_fetch = clone::PrepareFetch::new(
+        clone_url,
+        clone_path,
+        create::Kind::WithWorktree,
+        create::Options::default(),
+        gix::open::Options::default(),
+    )
+    .context("error preparing clone")?
+    .with_shallow(Shallow::DepthAtRemote(NonZeroU32::MIN))
+    .configure_remote(move |mut remote| {
+        remote.replace_refspecs(refspecs.iter().map(String::as_str), Direction::Fetch)?;
+        core::result::Result::Ok(remote.with_fetch_tags(Tags::None))
+    });
+
+    let repo = prepare_fetch
+        .fetch_then_checkout(Discard, &AtomicBool::new(false))?
+        .0
+        .main_worktree(Discard, &AtomicBool::new(false))?
+        .0;
+
+    // fetching later on, like for a ref pushed after this, should see every branch again
+    edit_repo_config(&repo, |config| {
+        let mut origin = config.section_mut_or_create_new("remote", Some("origin".into()))?;
+        while origin.remove("fetch").is_some() {}
+        origin.push(
+            "fetch".try_into()?,
+            Some("+refs/heads/*:refs/remotes/origin/*".into()),
+        );
+        Ok(())
+    })?;
+    Ok(repo)
+}
+
+/// Fetches whatever it takes for the dependency's ref to resolve: the ref itself if it was
+/// pushed after the clone was made, then more history if a shallow clone doesn't reach it
+fn fetch_until_resolvable(repo: &gix::Repository, haxelib: &Haxelib) -> Result<()> {
+    let vcs_ref = match haxelib.vcs_ref.as_ref() {
+        Some(vcs_ref) => vcs_ref,
+        None => return Ok(()),
+    };
+    if resolve_ref(repo, vcs_ref).is_ok() {
+        return Ok(());
+    }
+    fetch_missing_ref(repo, haxelib, vcs_ref)?;
 
+    for shallow in [Shallow::Deepen(50), Shallow::Deepen(500), Shallow::undo()] {
+        if resolve_ref(repo, vcs_ref).is_ok() || !repo.is_shallow() {
+            break;
+        }
+        println!(
+            "{}: {} isn't in the shallow clone, fetching more history",
+            haxelib.name.bold(),
+            vcs_ref.bold()
+        );
+        let stats = fetch_from_remote(repo, Tags::None, shallow)?;
+        print_fetch_stats(haxelib, &stats);
+    }
     Ok(())
 }
 
-#[tokio::main]
-pub async fn install_from_haxelib(haxelib: &Haxelib) -> Result<()> {
-    // braindead...
-    let mut target_url = String::from("https://lib.haxe.org/p/");
-    target_url.push_str(haxelib.name.as_str());
-    target_url.push('/');
-    target_url.push_str(haxelib.version.as_ref().unwrap().as_str());
-    target_url.push('/');
-    target_url.push_str("download");
+/// Fetches into the bare repo the cache keeps for this url, then clones the project's copy from it.
+/// A second project using the same repo only has to fetch what's new, or nothing at all when offline
+fn clone_through_cache(
+    cache: &Cache,
+    clone_url: Url,
+    clone_path: PathBuf,
+) -> Result<gix::Repository> {
+    let url = clone_url.to_bstring().to_string();
+    let cached_path = std::path::absolute(cache.repo_path(&url))?;
+    update_cached_repo(&cached_path, clone_url)?;
+    cache.touch_repo(&cached_path)?;
 
-    println!(
-        "Downloading: {} - {} - {}",
-        haxelib.name.bold(),
-        "lib.haxe.org".yellow().bold(),
-        target_url.bold()
-    );
+    let cached_url = Url::from_parts(
+        gix::url::Scheme::File,
+        None,
+        None,
+        None,
+        None,
+        BString::from(cached_path.display().to_string()),
+        false,
+    )?;
+    let repo = clone_repo(cached_url, clone_path)?;
 
-    let client = reqwest::Client::new();
+    // fetching later on (like `hmm-rs update`) should go to the real remote
+    set_origin_url(&repo, &url)?;
+    Ok(repo)
+}
 
-    let tmp_dir = env::temp_dir().join(format!("{}.zip", haxelib.name));
+fn update_cached_repo(cached_path: &Path, clone_url: Url) -> Result<()> {
+    if cached_path.exists() {
+        // whatever is cached is as up to date as it gets when offline
+        if config::settings().offline {
+            return Ok(());
+        }
 
-    let response = client.get(target_url).send().await?;
-    let total_size = response.content_length().unwrap();
-    // yoinked from haxeget !
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(ProgressStyle::with_template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.yellow/red}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-             .unwrap());
+        let cached_repo = open_git_repo(cached_path)?;
+        if let Err(e) = fetch_from_remote(&cached_repo, Tags::All, Shallow::NoChange) {
+            println!(
+                "{} {}",
+                "Couldn't update the cached repo, using it as is:".yellow(),
+                e.yellow()
+            );
+        }
+        return Ok(());
+    }
 
-    let mut file = File::create(tmp_dir.as_path())?;
-    let mut downloaded: u64 = 0;
-    let mut stream = response.bytes_stream();
+    config::ensure_online(&format!("clone {}", clone_url.to_bstring()))?;
+    println!("Caching {}", clone_url.to_bstring().bold());
+    std::fs::create_dir_all(cached_path)?;
+    let mut prepare_fetch = clone::PrepareFetch::new(
+        clone_url,
+        cached_path,
+        create::Kind::Bare,
+        create::Options::default(),
+        gix::open::Options::default(),
+    )
+    .context("error preparing clone")?
+    .configure_remote(|remote| {
+        // mirror the branches and tags, so the project clone sees them as the remote's own
+        core::result::Result::Ok(remote.with_refspecs(
+            ["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"],
+            Direction::Fetch,
+        )?)
+    });
 
-    while let Some(item) = stream.next().await {
-        let chunk = item?;
-        file.write_all(&chunk)?;
-        let new = std::cmp::min(downloaded + (chunk.len() as u64), total_size);
-        downloaded = new;
-        pb.set_position(new);
+    if let Err(e) = prepare_fetch.fetch_only(Discard, &AtomicBool::new(false)) {
+        // a half-made cached repo would get used as is next time
+        let _ = std::fs::remove_dir_all(cached_path);
+        return Err(e.into());
     }
+    Ok(())
+}
 
-    let finish_message = format!(
-        "{}: {} done downloading from {}",
-        haxelib.name.green().bold(),
-        haxelib.version.as_ref().unwrap().bright_green(),
-        "Haxelib".yellow().bold()
-    );
-    pb.finish_with_message(finish_message);
+fn set_origin_url(repo: &gix::Repository, url: &str) -> Result<()> {
+    edit_repo_config(repo, |config| {
+        config.set_raw_value(&"remote.origin.url", url)?;
+        Ok(())
+    })
+}
 
-    let version_as_commas = haxelib.version.as_ref().unwrap().replace(".", ",");
-    let mut output_dir: PathBuf = [".haxelib", haxelib.name.as_str()].iter().collect();
+/// Edits the repo's own `.git/config` file on disk
+fn edit_repo_config(
+    repo: &gix::Repository,
+    edit: impl FnOnce(&mut gix::config::File<'static>) -> Result<()>,
+) -> Result<()> {
+    let config_path = repo.path().join("config");
+    let mut config =
+        gix::config::File::from_path_no_includes(config_path.clone(), gix::config::Source::Local)?;
+    edit(&mut config)?;
+    config.write_to(&mut File::create(&config_path)?)?;
+    Ok(())
+}
 
-    if let Err(e) = std::fs::create_dir(&output_dir) {
-        if e.kind() == std::io::ErrorKind::AlreadyExists {
-            println!("Directory already exists: {:?}", output_dir.as_path());
-        } else {
-            return Err(anyhow!(
-                "Error creating directory: {:?}",
-                output_dir.as_path()
-            ));
+pub fn install_from_haxelib(haxelib: &Haxelib) -> Result<()> {
+    let version = haxelib.version.as_ref().ok_or(anyhow!(
+        "{} has `\"version\": null` in hmm.json, so there's no version to install. Set one, or run `hmm-rs haxelib {}` for the latest",
+        haxelib.name,
+        haxelib.name
+    ))?;
+
+    let cache = Cache::enabled()?;
+    let cached = cache
+        .as_ref()
+        .and_then(|cache| cache.find_zip(&haxelib.name, version));
+
+    // kept around until the zip is extracted, when there's no cache to move it into
+    let mut uncached_download = None;
+    let (zip_path, sha256, target_url) = match cached {
+        Some(cached) => {
+            println!(
+                "{}: {} found in the cache",
+                haxelib.name.green().bold(),
+                version.bright_green()
+            );
+            // zips cached before their url was kept can only guess at it
+            let target_url = cached
+                .url
+                .unwrap_or_else(|| registry::registries()[0].archive_url(&haxelib.name, version));
+            (cached.path, cached.sha256, target_url)
         }
-    }
+        None => {
+            let download = match cache.as_ref() {
+                Some(cache) => cache.download_file(&haxelib.name, version)?,
+                None => tempfile::NamedTempFile::new()?,
+            };
+            let target_url = download_from_registries(haxelib, download.path())?;
+            let sha256 = sha256_file(download.path())?;
+            match cache.as_ref() {
+                Some(cache) => (
+                    cache.store_zip(download, &haxelib.name, version, &sha256, &target_url)?,
+                    sha256,
+                    target_url,
+                ),
+                None => {
+                    let zip_path = download.path().to_path_buf();
+                    uncached_download = Some(download);
+                    (zip_path, sha256, target_url)
+                }
+            }
+        }
+    };
+
+    let version_as_commas = version.replace(".", ",");
+    let mut output_dir = haxelib.lib_path();
 
-    create_current_file(&output_dir, haxelib.version.as_ref().unwrap())?;
+    std::fs::create_dir_all(&output_dir).context(format!(
+        "Error creating directory: {:?}",
+        output_dir.as_path()
+    ))?;
+
+    create_current_file(&output_dir, version)?;
 
     // unzipping
     output_dir = output_dir.join(version_as_commas.as_str());
 
-    let archive = File::open(tmp_dir.as_path())?;
+    let archive = File::open(zip_path.as_path())?;
     let mut zip_file = ZipArchive::new(archive).context("Error opening zip file")?;
     zip_file
         .extract(output_dir.as_path())
         .context("Error extracting zip file")?;
 
-    std::fs::remove_file(tmp_dir.as_path())?;
+    let record = DownloadRecord {
+        url: target_url,
+        sha256,
+    };
+    record.write(&output_dir)?;
+
+    // cached zips stick around for the next install
+    drop(uncached_download);
     println!();
     println!(
         "{}: {} installed {}",
         haxelib.name.green().bold(),
-        haxelib.version.as_ref().unwrap().bright_green(),
+        version.bright_green(),
         Emoji("✅", "[✔️]")
     );
     // print an empty line, for readability between downloads
@@ -224,25 +987,83 @@ pub async fn install_from_haxelib(haxelib: &Haxelib) -> Result<()> {
     Ok(())
 }
 
+/// Tries each registry in order until one has the zip, returning the url it came from
+fn download_from_registries(haxelib: &Haxelib, download_path: &Path) -> Result<String> {
+    let version = haxelib.version.as_deref().unwrap_or_default();
+    let mut errors = Vec::new();
+    for registry in registry::registries() {
+        match registry.fetch_archive(&haxelib.name, version, download_path) {
+            core::result::Result::Ok(()) => return Ok(registry.archive_url(&haxelib.name, version)),
+            Err(e) => {
+                println!(
+                    "{}: couldn't download from {}: {}",
+                    haxelib.name.yellow().bold(),
+                    registry.name().bold(),
+                    e
+                );
+                errors.push(format!("  {}: {}", registry.name(), e));
+            }
+        }
+    }
+
+    Err(anyhow!(
+        "{} {} couldn't be downloaded from any registry:\n{}",
+        haxelib.name,
+        version,
+        errors.join("\n")
+    ))
+}
+
+/// Points `.haxelib/<name>/.dev` at the library folder from the `dir` field
+pub fn install_from_dev(haxelib: &Haxelib, json_path: &Path) -> Result<()> {
+    let dev_path = haxelib
+        .dev_path(json_path)
+        .ok_or(anyhow!("No dir provided for {}", haxelib.name))?;
+
+    if !dev_path.join("haxelib.json").exists() {
+        return Err(anyhow!(
+            "{}: {} does not contain a haxelib.json",
+            haxelib.name.red().bold(),
+            dev_path.display()
+        ));
+    }
+
+    let lib_path = haxelib.lib_path();
+    std::fs::create_dir_all(&lib_path)?;
+    let mut dev_file = File::create(lib_path.join(".dev"))?;
+    write!(dev_file, "{}", dev_path.display())?;
+
+    println!(
+        "{}: {} installed as dev {}",
+        haxelib.name.green().bold(),
+        dev_path.display().bright_green(),
+        Emoji("✅", "[✔️]")
+    );
+    Ok(())
+}
+
 pub fn install_from_git_using_gix_checkout(haxelib: &Haxelib) -> Result<()> {
     println!("Updating {} from git using checkout", haxelib.name);
 
-    let discover_result = gix::discover(
-        Path::new(".haxelib")
-            .join(haxelib.name.as_str())
-            .join("git"),
-    );
+    let repo_path = haxelib.lib_path().join("git");
+    if !repo_path.join(".git").exists() {
+        return install_from_git_using_gix_clone(haxelib);
+    }
+    let repo = open_git_repo(&repo_path)?;
 
-    let repo = match discover_result {
-        core::result::Result::Ok(r) => r,
-        Err(e) => {
-            if e.to_string().contains("not a git repository") {
-                return install_from_git_using_gix_clone(haxelib);
-            } else {
-                return Err(anyhow!("Error discovering git repo: {:?}", e));
-            }
+    // without a ref, it goes back to the remote's default branch like a fresh clone would
+    let with_default_branch;
+    let haxelib = match haxelib.vcs_ref {
+        Some(_) => haxelib,
+        None => {
+            with_default_branch = Haxelib {
+                vcs_ref: Some(default_branch(&repo)?),
+                ..haxelib.clone()
+            };
+            &with_default_branch
         }
     };
+    fetch_until_resolvable(&repo, haxelib)?;
 
     // let fetch_url = repo
     //     .find_fetch_remote(None)?
@@ -251,6 +1072,7 @@ pub fn install_from_git_using_gix_checkout(haxelib: &Haxelib) -> Result<()> {
     //     .clone();
 
     do_commit_checkout(&repo, haxelib)?;
+    update_submodules(&repo, haxelib)?;
 
     println!(
         "{}: {} updated {}",
@@ -262,20 +1084,301 @@ pub fn install_from_git_using_gix_checkout(haxelib: &Haxelib) -> Result<()> {
     Ok(())
 }
 
+/// The branch the remote's HEAD points at, as recorded by the clone,
+/// falling back to the branch that's checked out
+fn default_branch(repo: &gix::Repository) -> Result<String> {
+    if let core::result::Result::Ok(Some(remote_head)) =
+        repo.try_find_reference("refs/remotes/origin/HEAD")
+    {
+        if let Some(target) = remote_head.target().try_name() {
+            let name = target.shorten().to_string();
+            return Ok(name.trim_start_matches("origin/").to_string());
+        }
+    }
+
+    let head_name = repo.head_name()?.ok_or(anyhow!(
+        "{} has no ref in hmm.json and no default branch to check out",
+        repo.path().display()
+    ))?;
+    Ok(head_name.shorten().to_string())
+}
+
+/// Opens a git repo, with a fallback committer so fetches can write reflogs
+/// on machines that don't have a git identity configured (like CI)
+pub fn open_git_repo(path: &Path) -> Result<gix::Repository> {
+    let mut repo = gix::open(path)?;
+
+    if repo.committer().is_none() {
+        let mut config = gix::config::File::new(gix::config::file::Metadata::api());
+        config.set_raw_value(&Committer::NAME_FALLBACK, "hmm-rs")?;
+        config.set_raw_value(&Committer::EMAIL_FALLBACK, "hmm-rs@example.com")?;
+
+        let mut repo_config = repo.config_snapshot_mut();
+        repo_config.append(config);
+        repo_config.commit()?;
+    }
+
+    Ok(repo)
+}
+
+/// How much a fetch downloaded
+#[derive(Debug, Default)]
+pub struct FetchStats {
+    pub objects: u32,
+    pub bytes: u64,
+}
+
+/// Fetches new commits and refs from the repo's remote, `tags` picks which tags come along
+/// and `shallow` how much history
+pub fn fetch_from_remote(
+    repo: &gix::Repository,
+    tags: Tags,
+    shallow: Shallow,
+) -> Result<FetchStats> {
+    config::ensure_online("fetch from the git remote")?;
+    let outcome = repo
+        .find_fetch_remote(None)?
+        .with_fetch_tags(tags)
+        .connect(Direction::Fetch)?
+        .prepare_fetch(Discard, Default::default())?
+        .with_shallow(shallow)
+        .receive(Discard, &AtomicBool::new(false))?;
+
+    Ok(match outcome.status {
+        Status::Change {
+            write_pack_bundle, ..
+        } => FetchStats {
+            objects: write_pack_bundle.index.num_objects,
+            bytes: write_pack_bundle
+                .data_path
+                .and_then(|pack| std::fs::metadata(pack).ok())
+                .map_or(0, |metadata| metadata.len()),
+        },
+        // the remote had nothing new
+        _ => FetchStats::default(),
+    })
+}
+
+/// Fetches a ref that was pushed after the clone was made
+fn fetch_missing_ref(repo: &gix::Repository, haxelib: &Haxelib, vcs_ref: &str) -> Result<()> {
+    // commits come along with the branches, anything else might be a tag
+    let tags = match looks_like_sha(vcs_ref) {
+        true => Tags::Included,
+        false => Tags::All,
+    };
+    // a shallow clone only needs the new tips, not their whole history
+    let shallow = match repo.is_shallow() {
+        true => Shallow::DepthAtRemote(NonZeroU32::MIN),
+        false => Shallow::NoChange,
+    };
+
+    println!(
+        "{}: {} isn't in the local clone, fetching",
+        haxelib.name.bold(),
+        vcs_ref.bold()
+    );
+    let stats = fetch_from_remote(repo, tags, shallow)?;
+    print_fetch_stats(haxelib, &stats);
+    Ok(())
+}
+
+fn print_fetch_stats(haxelib: &Haxelib, stats: &FetchStats) {
+    println!(
+        "{}: fetched {} objects ({})",
+        haxelib.name.bold(),
+        stats.objects.bold(),
+        human_bytes(stats.bytes as f64).bold()
+    );
+}
+
+/// Lists the refs on the repo's remote and the commits they point to, without fetching anything
+pub fn list_remote_refs(repo: &gix::Repository) -> Result<BTreeMap<String, ObjectId>> {
+    list_refs(repo.find_fetch_remote(None)?)
+}
+
+/// Lists the refs of a repo that hasn't been cloned, through an empty scratch repo
+fn list_refs_at_url(url: Url) -> Result<BTreeMap<String, ObjectId>> {
+    let scratch = tempfile::tempdir()?;
+    let repo = gix::init_bare(scratch.path())?;
+    list_refs(repo.remote_at(url)?)
+}
+
+fn list_refs(remote: gix::Remote<'_>) -> Result<BTreeMap<String, ObjectId>> {
+    config::ensure_online("list the refs on the git remote")?;
+    // tags aren't part of the default fetch refspec, and a remote made from a url has none at all
+    let extra_refspecs = [
+        "+refs/heads/*:refs/remotes/origin/*",
+        "refs/tags/*:refs/tags/*",
+    ]
+    .into_iter()
+    .map(|spec| Ok(gix::refspec::parse(spec.into(), Operation::Fetch)?.to_owned()))
+    .collect::<Result<Vec<_>>>()?;
+    let options = gix::remote::ref_map::Options {
+        extra_refspecs,
+        ..Default::default()
+    };
+
+    let (ref_map, _) = remote
+        .connect(Direction::Fetch)?
+        .ref_map(Discard, options)?;
+
+    let mut remote_refs = BTreeMap::new();
+    for remote_ref in ref_map.remote_refs.iter() {
+        // annotated tags get peeled to the commit they point at
+        if let (name, Some(target), peeled) = remote_ref.unpack() {
+            let commit = peeled.unwrap_or(target).to_owned();
+            remote_refs.insert(name.to_string(), commit);
+        }
+    }
+
+    Ok(remote_refs)
+}
+
+/// What a git dependency's ref turned out to be
+pub enum ResolvedRef {
+    /// A local or remote branch, which HEAD follows
+    Branch { name: String, commit: ObjectId },
+    /// A tag or a commit, which HEAD gets detached at
+    Detached(ObjectId),
+}
+
+impl ResolvedRef {
+    pub fn commit(&self) -> ObjectId {
+        match self {
+            ResolvedRef::Branch { commit, .. } => *commit,
+            ResolvedRef::Detached(commit) => *commit,
+        }
+    }
+}
+
+/// Resolves a branch, tag, or full or abbreviated commit SHA to the commit it points at.
+/// Branches win over tags, which win over SHAs, same as `git checkout`
+pub fn resolve_ref(repo: &gix::Repository, vcs_ref: &str) -> Result<ResolvedRef> {
+    let candidates = [
+        (format!("refs/heads/{}", vcs_ref), true),
+        (format!("refs/remotes/origin/{}", vcs_ref), true),
+        (format!("refs/tags/{}", vcs_ref), false),
+    ];
+    for (name, is_branch) in candidates {
+        // refs like `HEAD~1` aren't valid reference names, so they fall through to rev_parse
+        let mut reference = match repo.try_find_reference(name.as_str()) {
+            core::result::Result::Ok(Some(reference)) => reference,
+            _ => continue,
+        };
+        // annotated tags point at a tag object, not the commit
+        let commit = reference.peel_to_commit()?.id;
+        return Ok(match is_branch {
+            true => ResolvedRef::Branch {
+                name: format!("refs/heads/{}", vcs_ref),
+                commit,
+            },
+            false => ResolvedRef::Detached(commit),
+        });
+    }
+
+    let commit = repo
+        .rev_parse_single(vcs_ref)
+        .context(format!("{} is not a branch, tag or commit", vcs_ref))?
+        .object()?
+        .peel_to_commit()?
+        .id;
+    Ok(ResolvedRef::Detached(commit))
+}
+
 fn do_commit_checkout(repo: &gix::Repository, haxelib: &Haxelib) -> Result<()> {
     print!("Checking out {}", haxelib.name);
-    if let Some(target_ref) = haxelib.vcs_ref.as_ref() {
-        println!(" at {}", target_ref);
-        let reflog_msg = BString::from("derp?");
+    let target_ref = match haxelib.vcs_ref.as_ref() {
+        Some(target_ref) => target_ref,
+        None => {
+            println!();
+            return Ok(());
+        }
+    };
+    println!(" at {}", target_ref);
 
-        let target_gix_ref = repo.find_reference(target_ref)?.id();
+    let reflog_msg = format!("hmm-rs: checkout {}", target_ref);
+    let commit = match resolve_ref(repo, target_ref)? {
+        ResolvedRef::Branch { name, commit } => {
+            // a branch that's only on the remote gets a local branch, like `git checkout` does
+            repo.edit_reference(update_ref(&name, Target::Object(commit), &reflog_msg)?)?;
+            repo.edit_reference(update_ref(
+                "HEAD",
+                Target::Symbolic(name.as_str().try_into()?),
+                &reflog_msg,
+            )?)?;
+            commit
+        }
+        ResolvedRef::Detached(commit) => {
+            repo.edit_reference(update_ref("HEAD", Target::Object(commit), &reflog_msg)?)?;
+            commit
+        }
+    };
 
-        repo.head_ref()
-            .unwrap()
-            .unwrap()
-            .set_target_id(target_gix_ref, reflog_msg)?;
+    checkout_worktree(repo, commit)
+}
+
+fn update_ref(name: &str, target: Target, message: &str) -> Result<RefEdit> {
+    Ok(RefEdit {
+        change: Change::Update {
+            log: LogChange {
+                mode: RefLog::AndReference,
+                force_create_reflog: false,
+                message: message.into(),
+            },
+            expected: PreviousValue::Any,
+            new: target,
+        },
+        name: name.try_into()?,
+        deref: false,
+    })
+}
+
+/// Makes the index and the files on disk match `commit`, deleting files it doesn't have
+fn checkout_worktree(repo: &gix::Repository, commit: ObjectId) -> Result<()> {
+    let workdir = repo
+        .workdir()
+        .ok_or(anyhow!("{} has no worktree", repo.path().display()))?;
+    let tree = repo.find_commit(commit)?.tree_id()?;
+    let old_index = repo.index_or_empty()?;
+    let mut index = repo.index_from_tree(&tree)?;
+
+    let new_paths: HashSet<&BStr> = index.entries().iter().map(|e| e.path(&index)).collect();
+    for entry in old_index.entries() {
+        let path = entry.path(&old_index);
+        if !new_paths.contains(path) {
+            remove_worktree_file(workdir, &gix::path::from_bstr(path))?;
+        }
+    }
+
+    let mut options =
+        repo.checkout_options(gix::worktree::stack::state::attributes::Source::IdMapping)?;
+    options.overwrite_existing = true;
+    gix::worktree::state::checkout(
+        &mut index,
+        workdir,
+        repo.objects.clone().into_arc()?,
+        &Discard,
+        &Discard,
+        &AtomicBool::new(false),
+        options,
+    )?;
+    index.write(Default::default())?;
+    Ok(())
+}
+
+/// Deletes a file that's no longer tracked, along with any folders it leaves empty
+fn remove_worktree_file(workdir: &Path, path: &Path) -> Result<()> {
+    let file = workdir.join(path);
+    // submodules are folders, and get left alone
+    if file.is_file() || file.is_symlink() {
+        std::fs::remove_file(&file).context(format!("Error removing {}", file.display()))?;
     }
 
+    for dir in path.ancestors().skip(1) {
+        if dir.as_os_str().is_empty() || std::fs::remove_dir(workdir.join(dir)).is_err() {
+            break;
+        }
+    }
     Ok(())
 }
 
@@ -285,3 +1388,65 @@ pub fn create_current_file(path: &Path, content: &String) -> Result<()> {
     write!(current_version_file, "{}", content)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clone_url() {
+        let parse = |url: &str| {
+            let haxelib = Haxelib {
+                name: "test".to_string(),
+                haxelib_type: HaxelibType::Git,
+                vcs_ref: None,
+                dir: None,
+                url: Some(url.to_string()),
+                version: None,
+                overrides: false,
+                shallow: None,
+            };
+            let url = clone_url(&haxelib).unwrap();
+            (
+                url.scheme.clone(),
+                url.host().map(str::to_string),
+                url.path.to_string(),
+            )
+        };
+        use gix::url::Scheme;
+
+        assert_eq!(
+            parse("https://github.com/openfl/lime"),
+            (
+                Scheme::Https,
+                Some("github.com".to_string()),
+                "/openfl/lime".to_string()
+            )
+        );
+        assert_eq!(parse("http://example.com/lime.git").0, Scheme::Http);
+        assert_eq!(
+            parse("git@github.com:org/private.git"),
+            (
+                Scheme::Ssh,
+                Some("github.com".to_string()),
+                "org/private.git".to_string()
+            )
+        );
+        assert_eq!(parse("ssh://git@github.com/org/private.git").0, Scheme::Ssh);
+        assert_eq!(parse("git://example.com/lime.git").0, Scheme::Git);
+        assert_eq!(
+            parse("file:///srv/git/lime.git"),
+            (Scheme::File, None, "/srv/git/lime.git".to_string())
+        );
+
+        let (scheme, _, path) = parse("../lime.git");
+        assert_eq!(scheme, Scheme::File);
+        assert_eq!(
+            path,
+            std::path::absolute("../lime.git")
+                .unwrap()
+                .display()
+                .to_string()
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use yansi::{Paint, Style};
+
+use crate::commands::install_command::{self, ResolvedRef};
+use crate::commands::print_table;
+use crate::hmm::{
+    self,
+    dependencies::Dependancies,
+    haxelib::{Haxelib, HaxelibType},
+};
+
+/// A single row of the before / after table printed once locking is done
+struct LockChange {
+    name: String,
+    before: String,
+    after: String,
+}
+
+pub fn lock_dependencies(
+    libs: &Option<Vec<String>>,
+    mut deps: Dependancies,
+    json_path: PathBuf,
+) -> Result<()> {
+    if let Some(libs) = libs {
+        for lib in libs {
+            deps.get_haxelib(lib).context(format!(
+                "{} not found in {}",
+                lib,
+                json_path.display()
+            ))?;
+        }
+    }
+
+    let mut changes = Vec::new();
+    let mut failed = Vec::new();
+
+    for haxelib in deps.dependencies.iter_mut() {
+        if let Some(libs) = libs {
+            if !libs.contains(&haxelib.name) {
+                continue;
+            }
+        }
+
+        let locked = match haxelib.haxelib_type {
+            HaxelibType::Haxelib => lock_haxelib(haxelib),
+            HaxelibType::Git => lock_git(haxelib),
+            _ => continue,
+        };
+
+        match locked {
+            Ok(Some(change)) => changes.push(change),
+            Ok(None) => (),
+            // locking everything skips what can't be locked, but asking for a library by name has to lock it
+            Err(e) if libs.is_some() => {
+                println!("{}: {}", haxelib.name.red().bold(), e.red());
+                failed.push(haxelib.name.clone());
+            }
+            Err(e) => println!("{}: {}", haxelib.name.yellow().bold(), e.yellow()),
+        }
+    }
+
+    let failures = match failed.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "{} dependencie(s) couldn't be locked: {}",
+            failed.len().bold(),
+            failed.join(", ")
+        )),
+    };
+
+    if changes.is_empty() {
+        if failed.is_empty() {
+            println!("All dependencies are already locked");
+        }
+        return failures;
+    }
+
+    print_table(&["Library", "Before", "After"], &table_rows(&changes));
+    hmm::json::save_json(deps, json_path)?;
+    failures
+}
+
+/// Pins a haxelib with `"version": null` to the version in its `.current` file
+fn lock_haxelib(haxelib: &mut Haxelib) -> Result<Option<LockChange>> {
+    if haxelib.version.is_some() {
+        return Ok(None);
+    }
+
+    let current_file = haxelib.lib_path().join(".current");
+    let current_version = fs::read_to_string(&current_file)
+        .map_err(|_| anyhow!("is not installed, so it can't be locked"))?;
+    let current_version = current_version.trim().to_string();
+
+    haxelib.version = Some(current_version.clone());
+
+    Ok(Some(LockChange {
+        name: haxelib.name.clone(),
+        before: "null".to_string(),
+        after: current_version,
+    }))
+}
+
+/// Pins a git dependency on a branch or tag to the commit it resolves to in the local clone
+fn lock_git(haxelib: &mut Haxelib) -> Result<Option<LockChange>> {
+    let repo_path = haxelib.lib_path().join("git");
+    let repo =
+        gix::open(&repo_path).map_err(|_| anyhow!("is not cloned, so it can't be locked"))?;
+
+    let commit = match haxelib.vcs_ref.as_ref() {
+        Some(vcs_ref) => match install_command::resolve_ref(&repo, vcs_ref)? {
+            // already pointing at the full commit
+            ResolvedRef::Detached(commit) if commit.to_string() == *vcs_ref => return Ok(None),
+            ResolvedRef::Branch { commit, .. } | ResolvedRef::Detached(commit) => commit,
+        },
+        None => repo.head_commit()?.id,
+    };
+
+    let before = haxelib
+        .vcs_ref
+        .clone()
+        .unwrap_or_else(|| "null".to_string());
+    haxelib.vcs_ref = Some(commit.to_string());
+
+    Ok(Some(LockChange {
+        name: haxelib.name.clone(),
+        before,
+        after: commit.to_string(),
+    }))
+}
+
+fn table_rows(changes: &[LockChange]) -> Vec<(Vec<String>, Style)> {
+    changes
+        .iter()
+        .map(|c| {
+            let cells = vec![c.name.clone(), c.before.clone(), c.after.clone()];
+            (cells, Style::new().green())
+        })
+        .collect()
+}
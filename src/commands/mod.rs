@@ -1,7 +1,51 @@
+pub mod cache_command;
 pub mod check_command;
 pub mod clean_command;
+pub mod dev_command;
+pub mod fromhxml_command;
+pub mod git_command;
 pub mod haxelib_command;
 pub mod init_command;
 pub mod install_command;
+pub mod lock_command;
+pub mod outdated_command;
+pub mod reinstall_command;
 pub mod remove_command;
 pub mod tohxml_command;
+pub mod update_command;
+
+use yansi::{Paint, Style};
+
+/// How commands that produce a report print it
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Human readable output, with colors
+    Text,
+    /// JSON, for CI and other tools to consume
+    Json,
+}
+
+/// Prints `rows` in columns lined up under a bold header line, each row in its own style
+pub fn print_table(headers: &[&str], rows: &[(Vec<String>, Style)]) {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for (cells, _) in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(cells.iter()) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let pad = |cells: Vec<&str>| -> String {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:width$}", cell))
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+
+    println!("{}", pad(headers.to_vec()).bold());
+    for (cells, style) in rows.iter() {
+        let line = pad(cells.iter().map(|cell| cell.as_str()).collect());
+        println!("{}", line.paint(*style));
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use gix::ObjectId;
+use serde::Serialize;
+use yansi::{Paint, Style};
+
+use crate::commands::check_command::{self, InstallType};
+use crate::commands::{haxelib_command, install_command, print_table, OutputFormat};
+use crate::hmm::dependencies::Dependancies;
+use crate::hmm::haxelib::{Haxelib, HaxelibType};
+
+#[derive(Serialize)]
+pub struct OutdatedReport {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub haxelib_type: HaxelibType,
+    /// The version or ref in hmm.json
+    pub pinned: Option<String>,
+    pub installed: Option<String>,
+    /// The newest version on lib.haxe.org, or the newest commit / tag on the git remote
+    pub latest: Option<String>,
+    pub outdated: bool,
+    /// Why the latest version couldn't be found, like the registry or remote being unreachable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl OutdatedReport {
+    fn failed(haxelib: &Haxelib, error: &anyhow::Error) -> Self {
+        let pinned = match haxelib.haxelib_type {
+            HaxelibType::Git => haxelib.vcs_ref.clone(),
+            _ => haxelib.version.clone(),
+        };
+        OutdatedReport {
+            name: haxelib.name.clone(),
+            haxelib_type: haxelib.haxelib_type.clone(),
+            pinned,
+            installed: None,
+            latest: None,
+            outdated: false,
+            error: Some(format!("{:#}", error)),
+        }
+    }
+}
+
+/// Reports dependencies that have newer versions available, without changing anything
+pub fn report_outdated(deps: &Dependancies, json_path: &Path, format: OutputFormat) -> Result<()> {
+    let mut reports = Vec::new();
+
+    for haxelib in deps.dependencies.iter() {
+        let report = match haxelib.haxelib_type {
+            HaxelibType::Haxelib => haxelib_report(haxelib, json_path),
+            HaxelibType::Git => git_report(haxelib),
+            _ => continue,
+        };
+
+        match report {
+            Ok(report) => reports.push(report),
+            Err(e) => {
+                // printed to stderr so it doesn't end up in the middle of the json
+                eprintln!("{}: {:#}", haxelib.name.red().bold(), e.red());
+                reports.push(OutdatedReport::failed(haxelib, &e));
+            }
+        }
+    }
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+        OutputFormat::Text => print_table(
+            &["Library", "Type", "Pinned", "Installed", "Latest"],
+            &table_rows(&reports),
+        ),
+    }
+
+    // "up to date" can't be told apart from "couldn't check" otherwise
+    let failed_count = reports.iter().filter(|r| r.error.is_some()).count();
+    if failed_count > 0 {
+        return Err(anyhow!(
+            "{} {} couldn't be checked for newer versions",
+            failed_count.bold(),
+            match failed_count {
+                1 => "dependency",
+                _ => "dependencies",
+            }
+        ));
+    }
+
+    let outdated_count = reports.iter().filter(|r| r.outdated).count();
+    if outdated_count > 0 {
+        return Err(anyhow!(
+            "{} {} outdated",
+            outdated_count.bold(),
+            match outdated_count {
+                1 => "dependency is",
+                _ => "dependencies are",
+            }
+        ));
+    }
+    Ok(())
+}
+
+fn haxelib_report(haxelib: &Haxelib, json_path: &Path) -> Result<OutdatedReport> {
+    let status = check_command::check_dependency(haxelib, json_path)?;
+    let installed = match status.install_type {
+        InstallType::AlreadyInstalled => status.wants,
+        _ => status.installed,
+    };
+
+    let latest = haxelib_command::get_latest_version(&haxelib.name)?;
+    let current = haxelib.version.as_ref().or(installed.as_ref());
+    let outdated = current != Some(&latest);
+
+    Ok(OutdatedReport {
+        name: haxelib.name.clone(),
+        haxelib_type: haxelib.haxelib_type.clone(),
+        pinned: haxelib.version.clone(),
+        installed,
+        latest: Some(latest),
+        outdated,
+        error: None,
+    })
+}
+
+/// Branches are compared against the newest commit on the remote branch,
+/// and tags against the highest semver tag on the remote.
+/// Commits are pinned on purpose, so they're never outdated
+fn git_report(haxelib: &Haxelib) -> Result<OutdatedReport> {
+    let repo = gix::open(haxelib.lib_path().join("git"))
+        .map_err(|_| anyhow!("is not cloned, run `hmm-rs install` first"))?;
+    let installed = repo.head_id()?.detach();
+    let remote_refs = install_command::list_remote_refs(&repo)?;
+
+    let mut report = OutdatedReport {
+        name: haxelib.name.clone(),
+        haxelib_type: haxelib.haxelib_type.clone(),
+        pinned: haxelib.vcs_ref.clone(),
+        installed: Some(installed.to_hex_with_len(7).to_string()),
+        latest: None,
+        outdated: false,
+        error: None,
+    };
+
+    let vcs_ref = match haxelib.vcs_ref.as_ref() {
+        Some(vcs_ref) => vcs_ref,
+        None => return Ok(report),
+    };
+
+    if let Some(newest) = remote_refs.get(&format!("refs/heads/{}", vcs_ref)) {
+        report.latest = Some(newest.to_hex_with_len(7).to_string());
+        report.outdated = *newest != installed;
+    } else if remote_refs.contains_key(&format!("refs/tags/{}", vcs_ref)) {
+        if let Some(newest_tag) = newest_semver_tag(&remote_refs) {
+            report.outdated = parse_tag(&newest_tag) > parse_tag(vcs_ref);
+            report.latest = Some(newest_tag);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Finds the tag with the highest version, ignoring tags that aren't semver
+fn newest_semver_tag(remote_refs: &BTreeMap<String, ObjectId>) -> Option<String> {
+    remote_refs
+        .keys()
+        .filter_map(|name| name.strip_prefix("refs/tags/"))
+        .filter(|tag| parse_tag(tag).is_some())
+        .max_by_key(|tag| parse_tag(tag))
+        .map(|tag| tag.to_string())
+}
+
+/// Parses tags like `v4.3.68` or `4.3.68`
+fn parse_tag(tag: &str) -> Option<semver::Version> {
+    semver::Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok()
+}
+
+/// The rows of the text table, outdated libraries in yellow and ones that couldn't be checked in red
+fn table_rows(reports: &[OutdatedReport]) -> Vec<(Vec<String>, Style)> {
+    reports
+        .iter()
+        .map(|r| {
+            let cells = vec![
+                r.name.clone(),
+                format!("{:?}", r.haxelib_type),
+                r.pinned.clone().unwrap_or_else(|| "null".to_string()),
+                r.installed.clone().unwrap_or_else(|| "None".to_string()),
+                match r.error {
+                    Some(_) => "error".to_string(),
+                    None => r.latest.clone().unwrap_or_else(|| "-".to_string()),
+                },
+            ];
+            let style = match (r.error.is_some(), r.outdated) {
+                (true, _) => Style::new().red(),
+                (false, true) => Style::new().yellow(),
+                (false, false) => Style::new(),
+            };
+            (cells, style)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_newest_semver_tag() {
+        let mut remote_refs = BTreeMap::new();
+        for name in [
+            "refs/heads/master",
+            "refs/tags/v4.3.9",
+            "refs/tags/v4.3.68",
+            "refs/tags/nightly",
+        ] {
+            remote_refs.insert(name.to_string(), ObjectId::null(gix::hash::Kind::Sha1));
+        }
+
+        assert_eq!(newest_semver_tag(&remote_refs), Some("v4.3.68".to_string()));
+    }
+}
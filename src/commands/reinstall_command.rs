@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use yansi::Paint;
+
+use crate::commands::install_command;
+use crate::hmm::dependencies::Dependancies;
+use crate::hmm::haxelib::{Haxelib, HaxelibType};
+use crate::hmm::lockfile;
+
+/// Force reinstalls dependencies, even if they are already installed at the right version
+pub fn reinstall_from_hmm(
+    deps: &Dependancies,
+    libs: &Option<Vec<String>>,
+    json_path: &Path,
+) -> Result<()> {
+    let to_reinstall: Vec<&Haxelib> = match libs {
+        Some(libs) => libs
+            .iter()
+            .map(|lib| {
+                deps.get_haxelib(lib).context(format!(
+                    "{} not found in {}",
+                    lib,
+                    json_path.display()
+                ))
+            })
+            .collect::<Result<_>>()?,
+        None => deps.dependencies.iter().collect(),
+    };
+
+    // versions get settled before anything is removed, so offline reinstalls know what has to be cached
+    let to_reinstall: Vec<Haxelib> = to_reinstall
+        .into_iter()
+        .map(|haxelib| match haxelib.haxelib_type {
+            HaxelibType::Haxelib => with_installed_version(haxelib),
+            _ => Ok(haxelib.clone()),
+        })
+        .collect::<Result<_>>()?;
+    install_command::ensure_cached(to_reinstall.iter())?;
+
+    println!(
+        "{} dependencies will be reinstalled",
+        to_reinstall.len().to_string().bold()
+    );
+
+    for haxelib in to_reinstall.iter() {
+        match haxelib.haxelib_type {
+            HaxelibType::Haxelib => reinstall_haxelib(haxelib)?,
+            HaxelibType::Git => reinstall_git(haxelib)?,
+            HaxelibType::Dev => install_command::install_from_dev(haxelib, json_path)?,
+            ref lib_type => return Err(install_command::unsupported_type(&haxelib.name, lib_type)),
+        }
+    }
+
+    lockfile::write_lockfile(deps, json_path)
+}
+
+/// `"version": null` reinstalls whatever version is currently installed
+fn with_installed_version(haxelib: &Haxelib) -> Result<Haxelib> {
+    let mut haxelib = haxelib.clone();
+    if haxelib.version.is_none() {
+        let current =
+            std::fs::read_to_string(haxelib.lib_path().join(".current")).map_err(|_| {
+                anyhow!(
+                    "{} has no version in hmm.json and isn't installed",
+                    haxelib.name
+                )
+            })?;
+        haxelib.version = Some(current.trim().to_string());
+    }
+    Ok(haxelib)
+}
+
+fn reinstall_haxelib(haxelib: &Haxelib) -> Result<()> {
+    let version = haxelib.version.as_deref().unwrap_or_default();
+    let version_path = haxelib.lib_path().join(version.replace(".", ","));
+    if version_path.exists() {
+        println!("Removing {}", version_path.display());
+        std::fs::remove_dir_all(&version_path)
+            .context(format!("Failed to remove {}", version_path.display()))?;
+    }
+
+    install_command::install_from_haxelib(haxelib)
+}
+
+fn reinstall_git(haxelib: &Haxelib) -> Result<()> {
+    let repo_path = haxelib.lib_path().join("git");
+    if repo_path.exists() {
+        println!("Removing {}", repo_path.display());
+        std::fs::remove_dir_all(&repo_path)
+            .context(format!("Failed to remove {}", repo_path.display()))?;
+    }
+
+    install_command::install_from_git_using_gix_clone(haxelib)
+}
@@ -1,5 +1,54 @@
-use anyhow::Result;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use yansi::Paint;
+
+use crate::hmm::{self, dependencies::Dependancies};
+
+pub fn remove_haxelibs(
+    libs: &[String],
+    keep_files: bool,
+    mut deps: Dependancies,
+    json_path: PathBuf,
+) -> Result<()> {
+    // Check every name up front, so a typo doesn't leave hmm.json half edited
+    let unknown_libs: Vec<&str> = libs
+        .iter()
+        .filter(|lib| deps.get_haxelib(lib).is_err())
+        .map(|lib| lib.as_str())
+        .collect();
+
+    if !unknown_libs.is_empty() {
+        return Err(anyhow!(
+            "{} not found in {}",
+            unknown_libs.join(", ").bold(),
+            json_path.display()
+        ));
+    }
+
+    let mut removed = Vec::new();
+    for lib in libs {
+        removed.push(deps.remove_haxelib(lib)?);
+    }
+
+    hmm::json::save_json(deps, json_path)?;
+
+    for haxelib in removed.iter() {
+        let lib_path = haxelib.lib_path();
+
+        if keep_files || !lib_path.exists() {
+            println!("{} removed from hmm.json", haxelib.name.bold());
+            continue;
+        }
+
+        std::fs::remove_dir_all(&lib_path)
+            .context(format!("Failed to remove {}", lib_path.display()))?;
+        println!(
+            "{} removed from hmm.json and {}",
+            haxelib.name.bold(),
+            lib_path.display()
+        );
+    }
 
-pub fn remove_haxelibs() -> Result<()> {
     Ok(())
 }
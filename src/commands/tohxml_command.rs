@@ -18,6 +18,8 @@ pub fn dump_to_hxml(deps: &Dependancies, hxml_out: Option<PathBuf>) -> Result<()
             }
             HaxelibType::Haxelib => lib_string
                 .push_str(format!(":{}", haxelib.version.as_ref().unwrap().as_str()).as_str()),
+            // haxe finds dev libraries through .haxelib/<name>/.dev, along with their dependencies and extraParams.hxml
+            HaxelibType::Dev => {}
             _ => {}
         }
         hxml.push_str(&lib_string);
@@ -0,0 +1,163 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use gix::refs::transaction::PreviousValue;
+use gix::remote::fetch::{Shallow, Tags};
+use yansi::Paint;
+
+use crate::commands::check_command::{self, InstallType};
+use crate::commands::{haxelib_command, install_command};
+use crate::hmm::{
+    self,
+    dependencies::Dependancies,
+    haxelib::{Haxelib, HaxelibType},
+};
+
+pub fn update_dependencies(
+    libs: &Option<Vec<String>>,
+    dry_run: bool,
+    mut deps: Dependancies,
+    json_path: PathBuf,
+) -> Result<()> {
+    if let Some(libs) = libs {
+        for lib in libs {
+            deps.get_haxelib(lib).context(format!(
+                "{} not found in {}",
+                lib,
+                json_path.display()
+            ))?;
+        }
+    }
+
+    let mut updated = 0;
+    let mut json_changed = false;
+    let mut failed = Vec::new();
+
+    for haxelib in deps.dependencies.iter_mut() {
+        if let Some(libs) = libs {
+            if !libs.contains(&haxelib.name) {
+                continue;
+            }
+        }
+
+        let update = match haxelib.haxelib_type {
+            HaxelibType::Haxelib => update_haxelib(haxelib, dry_run, &json_path),
+            HaxelibType::Git => update_git(haxelib, dry_run),
+            _ => continue,
+        };
+
+        match update {
+            Ok(Some((from, to))) => {
+                println!("{}: {} -> {}", haxelib.name.bold(), from.red(), to.green());
+                updated += 1;
+                json_changed |= haxelib.haxelib_type == HaxelibType::Haxelib;
+            }
+            Ok(None) => println!("{} is up to date", haxelib.name.bold()),
+            Err(e) => {
+                println!("{}: {}", haxelib.name.red().bold(), e.red());
+                failed.push(haxelib.name.clone());
+            }
+        }
+    }
+
+    let failures = match failed.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "{} dependencie(s) failed to update: {}",
+            failed.len().bold(),
+            failed.join(", ")
+        )),
+    };
+
+    if dry_run {
+        println!(
+            "{} dependencie(s) can be updated, run without `--dry-run` to update them",
+            updated.bold()
+        );
+        return failures;
+    }
+
+    println!("{} dependencie(s) updated", updated.bold());
+    // a dependency that failed to update can leave things half installed, so the lockfile stays as it was
+    let locked = failures.and_then(|_| {
+        hmm::lockfile::write_lockfile(&deps, &json_path).context("hmm-lock.json not updated")
+    });
+    // the ones that did update are installed, so hmm.json gets their new versions,
+    // the ones that failed keep the version they had
+    if json_changed {
+        hmm::json::save_json(deps, json_path)?;
+    }
+    locked
+}
+
+/// Moves a haxelib to the latest version on lib.haxe.org
+fn update_haxelib(
+    haxelib: &mut Haxelib,
+    dry_run: bool,
+    json_path: &Path,
+) -> Result<Option<(String, String)>> {
+    let latest_version = haxelib_command::get_latest_version(&haxelib.name)?;
+    if haxelib.version.as_ref() == Some(&latest_version) {
+        return Ok(None);
+    }
+
+    let previous_version = haxelib
+        .version
+        .clone()
+        .unwrap_or_else(|| "null".to_string());
+
+    if !dry_run {
+        // hmm.json only gets the new version once it's actually installed
+        let updated = Haxelib {
+            version: Some(latest_version.clone()),
+            ..haxelib.clone()
+        };
+        let install_status = check_command::check_haxelib(&updated, json_path)?;
+        if install_status.install_type != InstallType::AlreadyInstalled {
+            install_command::install_from_haxelib(&updated)?;
+        }
+    }
+    haxelib.version = Some(latest_version.clone());
+
+    Ok(Some((previous_version, latest_version)))
+}
+
+/// Moves a git dependency that's on a branch to the newest commit on that branch.
+/// Tags and commits are pinned on purpose, so they're left alone
+fn update_git(haxelib: &Haxelib, dry_run: bool) -> Result<Option<(String, String)>> {
+    let vcs_ref = match haxelib.vcs_ref.as_ref() {
+        Some(vcs_ref) => vcs_ref,
+        None => return Ok(None),
+    };
+
+    let repo = install_command::open_git_repo(&haxelib.lib_path().join("git"))
+        .map_err(|_| anyhow!("is not cloned, run `hmm-rs install` first"))?;
+
+    let branch = format!("refs/heads/{}", vcs_ref);
+    let remote_refs = install_command::list_remote_refs(&repo)?;
+    let newest_commit = match remote_refs.get(&branch) {
+        Some(commit) => *commit,
+        None => return Ok(None),
+    };
+
+    let current_commit = repo.head_id()?.detach();
+    if current_commit == newest_commit {
+        return Ok(None);
+    }
+
+    if !dry_run {
+        install_command::fetch_from_remote(&repo, Tags::Included, Shallow::NoChange)?;
+        repo.reference(
+            branch.as_str(),
+            newest_commit,
+            PreviousValue::Any,
+            "hmm-rs update",
+        )?;
+        install_command::install_from_git_using_gix_checkout(haxelib)?;
+    }
+
+    Ok(Some((
+        current_commit.to_hex_with_len(7).to_string(),
+        newest_commit.to_hex_with_len(7).to_string(),
+    )))
+}
@@ -0,0 +1,407 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+
+use super::config;
+
+/// The per-user download cache, shared between every project on the machine
+pub struct Cache {
+    root: PathBuf,
+}
+
+/// A bare git repository in the cache, mirroring a remote
+#[derive(Debug, Clone)]
+pub struct CachedRepo {
+    pub url: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub last_used: SystemTime,
+}
+
+/// Touched every time a cached repo gets cloned from, since fetching doesn't always happen
+const REPO_LAST_USED: &str = "hmm-last-used";
+
+/// A haxelib zip in the cache, stored as `haxelib/<name>/<version>/<sha256>.zip`,
+/// with the url it was downloaded from next to it in `<sha256>.url`
+#[derive(Debug, Clone)]
+pub struct CachedZip {
+    pub name: String,
+    pub version: String,
+    pub sha256: String,
+    pub path: PathBuf,
+    /// None for zips cached before urls were kept
+    pub url: Option<String>,
+    pub size: u64,
+    /// When the zip was last installed from, cached zips get touched every time they're used
+    pub last_used: SystemTime,
+}
+
+impl Cache {
+    pub fn new(root: PathBuf) -> Cache {
+        Cache { root }
+    }
+
+    /// `HMM_CACHE_DIR` if it's set, otherwise the user's cache folder, like `~/.cache/hmm-rs`
+    pub fn user() -> Result<Cache> {
+        let root = match std::env::var_os("HMM_CACHE_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => dirs::cache_dir()
+                .ok_or(anyhow!("Couldn't find a cache folder for this user"))?
+                .join("hmm-rs"),
+        };
+        Ok(Cache::new(root))
+    }
+
+    /// The user cache, or None if it was turned off with `--no-cache`
+    pub fn enabled() -> Result<Option<Cache>> {
+        match config::settings().no_cache {
+            true => Ok(None),
+            false => Cache::user().map(Some),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn haxelib_dir(&self) -> PathBuf {
+        self.root.join("haxelib")
+    }
+
+    fn zip_dir(&self, name: &str, version: &str) -> PathBuf {
+        self.haxelib_dir().join(name).join(version)
+    }
+
+    fn git_dir(&self) -> PathBuf {
+        self.root.join("git")
+    }
+
+    /// Where the bare repo mirroring `url` lives, a readable version of the url
+    /// followed by part of its hash, so urls that clean up the same don't collide
+    pub fn repo_path(&self, url: &str) -> PathBuf {
+        let readable: String = url
+            .split_once("://")
+            .map_or(url, |(_, rest)| rest)
+            .trim_end_matches('/')
+            .trim_end_matches(".git")
+            .chars()
+            .map(
+                |c| match c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+                    true => c,
+                    false => '-',
+                },
+            )
+            .take(64)
+            .collect();
+
+        let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+        self.git_dir()
+            .join(format!("{}-{}", readable.trim_matches('-'), &hash[..12]))
+    }
+
+    /// Marks a cached repo as used, so pruning leaves it alone
+    pub fn touch_repo(&self, repo_path: &Path) -> Result<()> {
+        std::fs::write(repo_path.join(REPO_LAST_USED), "")?;
+        Ok(())
+    }
+
+    /// Every git repo in the cache, sorted by url
+    pub fn list_repos(&self) -> Result<Vec<CachedRepo>> {
+        let mut repos: Vec<CachedRepo> = read_dirs(&self.git_dir())
+            .into_iter()
+            .filter_map(|path| {
+                let repo = gix::open(&path).ok()?;
+                let url = repo
+                    .find_remote("origin")
+                    .ok()?
+                    .url(gix::remote::Direction::Fetch)?
+                    .to_bstring()
+                    .to_string();
+                let last_used = std::fs::metadata(path.join(REPO_LAST_USED))
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                Some(CachedRepo {
+                    url,
+                    size: dir_size(&path),
+                    last_used,
+                    path,
+                })
+            })
+            .collect();
+
+        repos.sort_by(|a, b| a.url.cmp(&b.url));
+        Ok(repos)
+    }
+
+    /// Finds a cached zip of this version, making sure it still matches the SHA-256 it's stored under
+    pub fn find_zip(&self, name: &str, version: &str) -> Option<CachedZip> {
+        for cached in self.zips_in(&self.zip_dir(name, version)) {
+            if sha256_file(&cached.path).ok().as_ref() != Some(&cached.sha256) {
+                // corrupted somehow, so it gets downloaded again
+                let _ = remove_zip(&cached.path);
+                continue;
+            }
+
+            if let Ok(file) = File::options().write(true).open(&cached.path) {
+                let _ = file.set_modified(SystemTime::now());
+            }
+            return Some(cached);
+        }
+        None
+    }
+
+    /// A temp file to download a zip to, before we know its SHA-256.
+    /// Each download gets its own, so concurrent installs of the same version don't clash
+    pub fn download_file(&self, name: &str, version: &str) -> Result<NamedTempFile> {
+        let zip_dir = self.zip_dir(name, version);
+        std::fs::create_dir_all(&zip_dir)
+            .context(format!("Error creating cache folder {}", zip_dir.display()))?;
+        tempfile::Builder::new()
+            .prefix("download")
+            .suffix(".part")
+            .tempfile_in(&zip_dir)
+            .context(format!(
+                "Error creating a download in {}",
+                zip_dir.display()
+            ))
+    }
+
+    /// Moves a finished download into the cache, named after its SHA-256,
+    /// and remembers `url` so installing from the cache can say where it really came from
+    pub fn store_zip(
+        &self,
+        download: NamedTempFile,
+        name: &str,
+        version: &str,
+        sha256: &str,
+        url: &str,
+    ) -> Result<PathBuf> {
+        let zip_path = self.zip_dir(name, version).join(format!("{}.zip", sha256));
+        download.persist(&zip_path)?;
+        std::fs::write(zip_path.with_extension("url"), url)?;
+        Ok(zip_path)
+    }
+
+    /// Every zip in the cache, sorted by name and version
+    pub fn list_zips(&self) -> Result<Vec<CachedZip>> {
+        let mut zips = Vec::new();
+        for name_dir in read_dirs(&self.haxelib_dir()) {
+            for version_dir in read_dirs(&name_dir) {
+                zips.extend(self.zips_in(&version_dir));
+            }
+        }
+
+        zips.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+        Ok(zips)
+    }
+
+    /// Deletes the whole cache, returning how many bytes were freed
+    pub fn clear(&self) -> Result<u64> {
+        if !self.root.exists() {
+            return Ok(0);
+        }
+
+        let size = dir_size(&self.root);
+        std::fs::remove_dir_all(&self.root)
+            .context(format!("Error removing {}", self.root.display()))?;
+        Ok(size)
+    }
+
+    /// Deletes git repos that haven't been cloned from in `max_age`, returning what was removed
+    pub fn prune_repos(&self, max_age: Duration) -> Result<Vec<CachedRepo>> {
+        let now = SystemTime::now();
+        let mut removed = Vec::new();
+
+        for repo in self.list_repos()? {
+            let age = now.duration_since(repo.last_used).unwrap_or_default();
+            if age >= max_age {
+                std::fs::remove_dir_all(&repo.path)?;
+                removed.push(repo);
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Deletes zips that haven't been used in `max_age`, along with any interrupted downloads.
+    /// Returns what was removed
+    pub fn prune(&self, max_age: Duration) -> Result<Vec<CachedZip>> {
+        let now = SystemTime::now();
+        let mut removed = Vec::new();
+
+        for zip in self.list_zips()? {
+            let age = now.duration_since(zip.last_used).unwrap_or_default();
+            if age >= max_age {
+                remove_zip(&zip.path)?;
+                removed.push(zip);
+            }
+        }
+
+        for name_dir in read_dirs(&self.haxelib_dir()) {
+            for version_dir in read_dirs(&name_dir) {
+                let entries = std::fs::read_dir(&version_dir).into_iter().flatten();
+                for entry in entries.filter_map(|entry| entry.ok()) {
+                    if entry.path().extension().is_some_and(|ext| ext == "part") {
+                        let _ = std::fs::remove_file(entry.path());
+                    }
+                }
+                // only succeeds once the folder is empty
+                let _ = std::fs::remove_dir(&version_dir);
+            }
+            let _ = std::fs::remove_dir(&name_dir);
+        }
+
+        Ok(removed)
+    }
+
+    fn zips_in(&self, version_dir: &Path) -> Vec<CachedZip> {
+        let entries = match std::fs::read_dir(version_dir) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+
+        let name = folder_name(version_dir.parent());
+        let version = folder_name(Some(version_dir));
+
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension()? != "zip" {
+                    return None;
+                }
+                let metadata = entry.metadata().ok()?;
+                Some(CachedZip {
+                    name: name.clone(),
+                    version: version.clone(),
+                    sha256: path.file_stem()?.to_string_lossy().to_string(),
+                    url: std::fs::read_to_string(path.with_extension("url")).ok(),
+                    size: metadata.len(),
+                    last_used: metadata.modified().ok()?,
+                    path,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Deletes a cached zip along with the url stored next to it
+fn remove_zip(zip_path: &Path) -> Result<()> {
+    std::fs::remove_file(zip_path)?;
+    let _ = std::fs::remove_file(zip_path.with_extension("url"));
+    Ok(())
+}
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = <Sha256 as Digest>::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn folder_name(path: Option<&Path>) -> String {
+    path.and_then(|p| p.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn read_dirs(dir: &Path) -> Vec<PathBuf> {
+    match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_find_zip() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path().to_path_buf());
+        assert!(cache.find_zip("lime", "8.1.0").is_none());
+
+        let download = cache.download_file("lime", "8.1.0").unwrap();
+        std::fs::write(download.path(), "not really a zip").unwrap();
+        let sha256 = sha256_file(download.path()).unwrap();
+        let url = "https://mirror.internal/p/lime/8.1.0/download";
+        cache
+            .store_zip(download, "lime", "8.1.0", &sha256, url)
+            .unwrap();
+
+        let cached = cache.find_zip("lime", "8.1.0").unwrap();
+        assert_eq!(cached.sha256, sha256);
+        assert_eq!(cached.url.as_deref(), Some(url));
+        assert_eq!(cache.list_zips().unwrap().len(), 1);
+
+        // a zip that doesn't match its name gets thrown out
+        std::fs::write(&cached.path, "tampered").unwrap();
+        assert!(cache.find_zip("lime", "8.1.0").is_none());
+        assert!(cache.list_zips().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path().to_path_buf());
+
+        let download = cache.download_file("lime", "8.1.0").unwrap();
+        std::fs::write(download.path(), "zip").unwrap();
+        let sha256 = sha256_file(download.path()).unwrap();
+        cache
+            .store_zip(download, "lime", "8.1.0", &sha256, "")
+            .unwrap();
+        // an interrupted download never got to clean up after itself
+        let (_, interrupted) = cache
+            .download_file("openfl", "9.3.3")
+            .unwrap()
+            .keep()
+            .unwrap();
+        std::fs::write(interrupted, "half").unwrap();
+
+        assert!(cache.prune(Duration::from_secs(60)).unwrap().is_empty());
+        assert!(!cache.haxelib_dir().join("openfl").exists());
+
+        let removed = cache.prune(Duration::ZERO).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert!(cache.list_zips().unwrap().is_empty());
+        assert_eq!(cache.clear().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_repo_path() {
+        let cache = Cache::new(PathBuf::from("cache"));
+        let flixel = cache.repo_path("https://github.com/HaxeFlixel/flixel.git");
+        let name = flixel.file_name().unwrap().to_string_lossy();
+        assert!(name.starts_with("github.com-HaxeFlixel-flixel-"));
+        assert_eq!(flixel.parent(), Some(Path::new("cache/git")));
+
+        // the same repo over a different protocol is a separate mirror
+        assert_ne!(
+            flixel,
+            cache.repo_path("git@github.com:HaxeFlixel/flixel.git")
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+/// Options that change how every command behaves, set once at startup
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Never read from or write to the per-user download cache
+    pub no_cache: bool,
+    /// Never touch lib.haxe.org or git remotes, only install from the caches
+    pub offline: bool,
+    /// Haxelib registries to try in order, lib.haxe.org when empty
+    pub registries: Vec<String>,
+    /// Clone git dependencies at depth 1 with no tags, unless hmm.json says otherwise
+    pub shallow: bool,
+}
+
+impl Settings {
+    const DEFAULT: Settings = Settings {
+        no_cache: false,
+        offline: false,
+        registries: Vec::new(),
+        shallow: false,
+    };
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings::DEFAULT
+    }
+}
+
+static SETTINGS: RwLock<Settings> = RwLock::new(Settings::DEFAULT);
+
+/// Sets the settings for the rest of the run
+pub fn set(settings: Settings) {
+    *SETTINGS.write().unwrap_or_else(|e| e.into_inner()) = settings;
+}
+
+/// The settings for this run, or the defaults if they were never set
+pub fn settings() -> Settings {
+    SETTINGS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Errors when running offline, `action` is whatever needed the network
+pub fn ensure_online(action: &str) -> Result<()> {
+    match settings().offline {
+        true => Err(anyhow!("Can't {} while offline", action)),
+        false => Ok(()),
+    }
+}
+
+/// The per-user config file, for settings that should apply to every project
+#[derive(Deserialize, Default, Debug)]
+pub struct UserConfig {
+    #[serde(default)]
+    pub offline: bool,
+    /// Haxelib registries to try after the project's own, like an internal mirror
+    #[serde(default)]
+    pub registries: Vec<String>,
+    #[serde(default)]
+    pub shallow: bool,
+}
+
+impl UserConfig {
+    /// `HMM_CONFIG` if it's set, otherwise `config.json` in the user's config folder,
+    /// like `~/.config/hmm-rs/config.json`
+    pub fn path() -> Option<PathBuf> {
+        match std::env::var_os("HMM_CONFIG") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(dirs::config_dir()?.join("hmm-rs").join("config.json")),
+        }
+    }
+
+    /// Reads the user config, which is entirely optional
+    pub fn load() -> Result<UserConfig> {
+        let path = match UserConfig::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(UserConfig::default()),
+        };
+
+        let file = File::open(&path).context(format!("Error opening {}", path.display()))?;
+        serde_json::from_reader(file).context(format!("Error parsing {}", path.display()))
+    }
+}
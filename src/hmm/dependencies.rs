@@ -6,6 +6,9 @@ use std::fmt;
 #[derive(Serialize, Deserialize)]
 pub struct Dependancies {
     pub dependencies: Vec<Haxelib>,
+    /// Haxelib registries to try before the user's and lib.haxe.org, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub registries: Vec<String>,
 }
 
 impl fmt::Display for Dependancies {
@@ -40,6 +43,34 @@ impl Dependancies {
         Err(anyhow::anyhow!("Haxelib not found"))
     }
 
+    /// Removes a haxelib from the dependency list, returning the removed entry
+    pub fn remove_haxelib(&mut self, lib: &str) -> Result<Haxelib> {
+        match self
+            .dependencies
+            .iter()
+            .position(|haxelib| haxelib.name == lib)
+        {
+            Some(index) => Ok(self.dependencies.remove(index)),
+            None => Err(anyhow::anyhow!("Haxelib not found")),
+        }
+    }
+
+    /// Replaces the entry with the same name, or adds it to the end of the list if there isn't one.
+    /// Returns the entry that got replaced
+    pub fn upsert_haxelib(&mut self, haxelib: Haxelib) -> Option<Haxelib> {
+        match self
+            .dependencies
+            .iter_mut()
+            .find(|h| h.name == haxelib.name)
+        {
+            Some(existing) => Some(std::mem::replace(existing, haxelib)),
+            None => {
+                self.dependencies.push(haxelib);
+                None
+            }
+        }
+    }
+
     pub fn print_haxelib(lib: &Haxelib) {
         let version_or_ref = match &lib.version {
             Some(v) => format!("version: {}", v),
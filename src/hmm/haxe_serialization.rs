@@ -0,0 +1,281 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Context, Result};
+
+/// A value in haxe's serialization format, the parts of it haxelib's remoting api uses
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    /// Dates are kept as whatever haxe sent, either `YYYY-MM-DD hh:mm:ss` or a timestamp
+    Date(String),
+    /// Arrays and lists
+    Array(Vec<Value>),
+    /// Anonymous objects and class instances
+    Object(BTreeMap<String, Value>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// A field of an object, None for anything else
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.get(field),
+            _ => None,
+        }
+    }
+}
+
+pub fn serialize_string(s: &str) -> String {
+    let encoded = urlencoding::encode(s);
+    format!("y{}:{}", encoded.len(), encoded)
+}
+
+/// Serializes an array of strings, the only thing the remoting calls need to send
+pub fn serialize_strings(strings: &[&str]) -> String {
+    let items: String = strings.iter().map(|s| serialize_string(s)).collect();
+    format!("a{}h", items)
+}
+
+/// Reads a single serialized value, erroring on anything after it
+pub fn unserialize(input: &str) -> Result<Value> {
+    let mut parser = Parser {
+        input: input.as_bytes(),
+        pos: 0,
+        strings: Vec::new(),
+    };
+    let value = parser.value()?;
+    match parser.pos == input.len() {
+        true => Ok(value),
+        false => Err(anyhow!("Unexpected data at {} in {}", parser.pos, input)),
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    /// Strings seen so far, later ones can refer back to them with `R<index>`
+    strings: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Result<u8> {
+        let byte = *self
+            .input
+            .get(self.pos)
+            .ok_or(anyhow!("Serialized data ended early"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    /// Reads while `accept` matches, as a str
+    fn take_while(&mut self, accept: impl Fn(u8) -> bool) -> Result<&str> {
+        let start = self.pos;
+        while self.peek().is_some_and(&accept) {
+            self.pos += 1;
+        }
+        Ok(std::str::from_utf8(&self.input[start..self.pos])?)
+    }
+
+    fn int(&mut self) -> Result<i64> {
+        let digits = self.take_while(|b| b.is_ascii_digit() || b == b'-')?;
+        digits
+            .parse()
+            .context(format!("Invalid number {:?} in serialized data", digits))
+    }
+
+    /// A string length, reference or run of nulls, which can't be negative
+    fn count(&mut self) -> Result<usize> {
+        let count = self.int()?;
+        usize::try_from(count).map_err(|_| anyhow!("Invalid count {} in serialized data", count))
+    }
+
+    fn float(&mut self) -> Result<f64> {
+        let digits = self
+            .take_while(|b| b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.' | b'e' | b'E'))?;
+        digits
+            .parse()
+            .context(format!("Invalid number {:?} in serialized data", digits))
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        let value = match self.next()? {
+            b'n' => Value::Null,
+            b't' => Value::Bool(true),
+            b'f' => Value::Bool(false),
+            b'z' => Value::Int(0),
+            b'i' => Value::Int(self.int()?),
+            b'd' => Value::Float(self.float()?),
+            b'k' => Value::Float(f64::NAN),
+            b'm' => Value::Float(f64::NEG_INFINITY),
+            b'p' => Value::Float(f64::INFINITY),
+            b'y' => Value::String(self.string()?),
+            b'R' => Value::String(self.string_ref()?),
+            b'v' => self.date()?,
+            b'a' => Value::Array(self.array()?),
+            b'l' => Value::Array(self.list()?),
+            b'o' => Value::Object(self.fields()?),
+            b'c' => {
+                // class instances are just their fields, the class name doesn't matter here
+                self.value()?;
+                Value::Object(self.fields()?)
+            }
+            other => {
+                return Err(anyhow!(
+                    "Unsupported {:?} in serialized data",
+                    other as char
+                ))
+            }
+        };
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.count()?;
+        if self.next()? != b':' {
+            return Err(anyhow!("Expected : after string length"));
+        }
+        let end = self
+            .pos
+            .checked_add(len)
+            .ok_or(anyhow!("Serialized string is too long"))?;
+        let encoded = self
+            .input
+            .get(self.pos..end)
+            .ok_or(anyhow!("Serialized string ended early"))?;
+        self.pos = end;
+
+        let decoded = urlencoding::decode(std::str::from_utf8(encoded)?)?.to_string();
+        self.strings.push(decoded.clone());
+        Ok(decoded)
+    }
+
+    fn string_ref(&mut self) -> Result<String> {
+        let index = self.count()?;
+        self.strings.get(index).cloned().ok_or(anyhow!(
+            "Serialized data refers to missing string {}",
+            index
+        ))
+    }
+
+    fn date(&mut self) -> Result<Value> {
+        // older haxe versions write `YYYY-MM-DD hh:mm:ss`, newer ones a float timestamp
+        if self.input.get(self.pos + 4) == Some(&b'-') {
+            let date = self
+                .input
+                .get(self.pos..self.pos + 19)
+                .ok_or(anyhow!("Serialized date ended early"))?;
+            self.pos += 19;
+            return Ok(Value::Date(std::str::from_utf8(date)?.to_string()));
+        }
+        Ok(Value::Date(self.float()?.to_string()))
+    }
+
+    fn array(&mut self) -> Result<Vec<Value>> {
+        let mut values = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'h') => {
+                    self.pos += 1;
+                    return Ok(values);
+                }
+                // a run of nulls
+                Some(b'u') => {
+                    self.pos += 1;
+                    let nulls = self.count()?;
+                    values.extend(std::iter::repeat_n(Value::Null, nulls));
+                }
+                _ => values.push(self.value()?),
+            }
+        }
+    }
+
+    fn list(&mut self) -> Result<Vec<Value>> {
+        let mut values = Vec::new();
+        while self.peek() != Some(b'h') {
+            values.push(self.value()?);
+        }
+        self.pos += 1;
+        Ok(values)
+    }
+
+    fn fields(&mut self) -> Result<BTreeMap<String, Value>> {
+        let mut fields = BTreeMap::new();
+        while self.peek() != Some(b'g') {
+            let name = match self.value()? {
+                Value::String(name) => name,
+                other => return Err(anyhow!("Expected a field name, got {:?}", other)),
+            };
+            fields.insert(name, self.value()?);
+        }
+        self.pos += 1;
+        Ok(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serialize_strings(&["api", "getLatestVersion"]),
+            "ay3:apiy16:getLatestVersionh"
+        );
+        assert_eq!(serialize_string("a b"), "y5:a%20b");
+    }
+
+    #[test]
+    fn test_unserialize() {
+        assert_eq!(
+            unserialize("y5:8.1.0").unwrap(),
+            Value::String("8.1.0".to_string())
+        );
+
+        // trimmed down from what lib.haxe.org sends for `infos`
+        let infos = unserialize(
+            "oy4:namey4:limey4:descy11:A%20libraryy8:versionsaoR0y5:8.0.0y4:datev2023-01-01 00:00:00goR0y5:8.1.0R6d1.7e12gu2hy4:tagslR2hg",
+        )
+        .unwrap();
+        assert_eq!(infos.get("name").and_then(Value::as_str), Some("lime"));
+        assert_eq!(infos.get("desc").and_then(Value::as_str), Some("A library"));
+
+        let versions = infos.get("versions").and_then(Value::as_array).unwrap();
+        assert_eq!(versions.len(), 4);
+        assert_eq!(
+            versions[1].get("name").and_then(Value::as_str),
+            Some("8.1.0")
+        );
+        assert_eq!(versions[3], Value::Null);
+        assert_eq!(
+            infos.get("tags").and_then(Value::as_array),
+            Some(&[Value::String("desc".to_string())][..])
+        );
+
+        assert!(unserialize("y5:8.1.0extra").is_err());
+        assert!(unserialize("y-1:").is_err());
+        assert!(unserialize("y9223372036854775807:").is_err());
+        assert!(unserialize("R-1").is_err());
+    }
+}
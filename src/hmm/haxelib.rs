@@ -1,3 +1,5 @@
+use std::path::{Path, PathBuf};
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -13,6 +15,13 @@ pub struct Haxelib {
     pub url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub version: Option<String>,
+    /// Keeps this version even when other dependencies require a different one
+    #[serde(rename = "override", default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub overrides: bool,
+    /// Clone just the ref at depth 1 with no tags, overriding the global `--shallow` setting
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shallow: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -26,3 +35,23 @@ pub enum HaxelibType {
     #[serde(rename = "hg")]
     Mecurial,
 }
+
+impl Haxelib {
+    /// Haxelib folders replace . with , in the folder name
+    pub fn folder_name(&self) -> String {
+        self.name.replace(".", ",")
+    }
+
+    /// The path of this library inside the local `.haxelib/` folder
+    pub fn lib_path(&self) -> PathBuf {
+        Path::new(".haxelib").join(self.folder_name())
+    }
+
+    /// The absolute path a dev library points at,
+    /// with relative `dir` paths resolved against the folder hmm.json is in
+    pub fn dev_path(&self, json_path: &Path) -> Option<PathBuf> {
+        let dir = Path::new(self.dir.as_ref()?);
+        let json_dir = json_path.parent().unwrap_or(Path::new(""));
+        std::path::absolute(json_dir.join(dir)).ok()
+    }
+}
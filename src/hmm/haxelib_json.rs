@@ -0,0 +1,31 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// The parts of a library's own `haxelib.json` that we care about
+#[derive(Deserialize, Debug, Clone)]
+pub struct HaxelibJson {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    pub license: Option<String>,
+    #[serde(rename = "classPath")]
+    pub class_path: Option<String>,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, String>,
+}
+
+impl HaxelibJson {
+    /// Reads the haxelib.json file inside of `dir`
+    pub fn read(dir: &Path) -> Result<HaxelibJson> {
+        let json_path = dir.join("haxelib.json");
+        let file = File::open(&json_path).context(format!("{:?} not found", json_path))?;
+        let haxelib_json =
+            serde_json::from_reader(file).context(format!("Error parsing {:?}", json_path))?;
+        Ok(haxelib_json)
+    }
+}
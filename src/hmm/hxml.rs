@@ -0,0 +1,316 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+
+use super::haxelib::{Haxelib, HaxelibType};
+
+/// The libraries found while parsing one or more .hxml files
+#[derive(Default)]
+pub struct HxmlLibs {
+    pub libs: Vec<Haxelib>,
+    /// Libraries that were asked for at two different versions, (name, kept, ignored)
+    pub conflicts: Vec<(String, String, String)>,
+    /// Whether `--next` or `--each` split the hxml into several builds, whose libraries all got merged
+    pub split_builds: bool,
+    visited: HashSet<PathBuf>,
+}
+
+/// Flags that take a value, which mustn't be mistaken for an included .hxml
+const VALUE_FLAGS: &[&str] = &[
+    "-p",
+    "-cp",
+    "--class-path",
+    "-m",
+    "--main",
+    "-D",
+    "--define",
+    "-r",
+    "--resource",
+    "--macro",
+    "--cmd",
+    "--remap",
+    "--dce",
+    "--display",
+    "--xml",
+    "--json",
+    "--connect",
+    "--server-connect",
+    "--server-listen",
+    "--custom-target",
+    "-js",
+    "--js",
+    "-lua",
+    "--lua",
+    "-swf",
+    "--swf",
+    "-as3",
+    "--as3",
+    "-neko",
+    "--neko",
+    "-php",
+    "--php",
+    "-cpp",
+    "--cpp",
+    "-cppia",
+    "--cppia",
+    "-cs",
+    "--cs",
+    "-java",
+    "--java",
+    "-jvm",
+    "--jvm",
+    "-python",
+    "--python",
+    "-hl",
+    "--hl",
+    "-x",
+    "--swf-version",
+    "--swf-header",
+    "--swf-lib",
+    "--swf-lib-extern",
+    "--java-lib",
+    "--java-lib-extern",
+    "--net-lib",
+    "--net-std",
+    "--c-arg",
+    "--neko-lib-path",
+    "--hxb",
+    "--hxb-lib",
+];
+
+impl HxmlLibs {
+    /// Parses an .hxml file, following any other .hxml files it includes.
+    /// Like haxe, includes are relative to the working directory rather than the hxml
+    pub fn parse_file(&mut self, hxml_path: &Path) -> Result<()> {
+        self.parse_file_in(hxml_path, Path::new(""))
+    }
+
+    fn parse_file_in(&mut self, hxml_path: &Path, cwd: &Path) -> Result<()> {
+        let canonical = hxml_path
+            .canonicalize()
+            .context(format!("{} not found", hxml_path.display()))?;
+
+        // an hxml that includes itself (or a parent) would loop forever
+        if !self.visited.insert(canonical) {
+            return Ok(());
+        }
+
+        let contents = std::fs::read_to_string(hxml_path)
+            .context(format!("Error reading {}", hxml_path.display()))?;
+        self.parse_str(&contents, cwd)
+    }
+
+    /// Parses the contents of an .hxml file, with includes and `--cwd` relative to `cwd`
+    pub fn parse_str(&mut self, contents: &str, cwd: &Path) -> Result<()> {
+        let mut cwd = cwd.to_path_buf();
+        let mut args = contents.lines().flat_map(split_hxml_line);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-lib" | "-L" | "--library" => {
+                    let lib = args.next().ok_or(anyhow!("{} is missing a library", arg))?;
+                    self.add_lib(parse_lib(&lib)?);
+                }
+                "-C" | "--cwd" => {
+                    let dir = args.next().ok_or(anyhow!("{} is missing a path", arg))?;
+                    cwd = cwd.join(dir);
+                }
+                "--next" | "--each" => self.split_builds = true,
+                // everything after it is passed to the program being run
+                "--run" => break,
+                _ if VALUE_FLAGS.contains(&arg.as_str()) => {
+                    args.next();
+                }
+                _ if arg.ends_with(".hxml") && !arg.starts_with('-') => {
+                    let include = cwd.join(&arg);
+                    self.parse_file_in(&include, &cwd)?;
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_lib(&mut self, lib: Haxelib) {
+        let existing = match self.libs.iter_mut().find(|l| l.name == lib.name) {
+            Some(existing) => existing,
+            None => return self.libs.push(lib),
+        };
+
+        match (describe_lib(existing), describe_lib(&lib)) {
+            (_, None) => (),
+            (None, Some(_)) => *existing = lib,
+            (Some(kept), Some(ignored)) if kept != ignored => {
+                self.conflicts.push((lib.name.clone(), kept, ignored))
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Splits a line of an hxml file into its arguments, respecting quotes and comments
+fn split_hxml_line(line: &str) -> Vec<String> {
+    let line = line.trim();
+    if line.starts_with('#') {
+        return vec![];
+    }
+
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        args.push(current);
+    }
+    args
+}
+
+/// Parses a `-lib` argument, which is one of `name`, `name:version` or `name:git:url#ref`
+pub fn parse_lib(lib: &str) -> Result<Haxelib> {
+    let mut haxelib = Haxelib {
+        name: String::new(),
+        haxelib_type: HaxelibType::Haxelib,
+        vcs_ref: None,
+        dir: None,
+        url: None,
+        version: None,
+        overrides: false,
+        shallow: None,
+    };
+
+    let (name, rest) = match lib.split_once(':') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (lib, None),
+    };
+
+    if name.is_empty() {
+        return Err(anyhow!("Invalid library: {}", lib));
+    }
+    haxelib.name = name.to_string();
+
+    match rest {
+        Some(git) if git.starts_with("git:") => {
+            let git = &git["git:".len()..];
+            haxelib.haxelib_type = HaxelibType::Git;
+            match git.rsplit_once('#') {
+                Some((url, vcs_ref)) => {
+                    haxelib.url = Some(url.to_string());
+                    haxelib.vcs_ref = Some(vcs_ref.to_string());
+                }
+                None => haxelib.url = Some(git.to_string()),
+            }
+        }
+        Some(version) if !version.is_empty() => haxelib.version = Some(version.to_string()),
+        _ => (),
+    }
+
+    Ok(haxelib)
+}
+
+/// The version or git source of a lib, or None if it isn't pinned to anything
+fn describe_lib(lib: &Haxelib) -> Option<String> {
+    match lib.haxelib_type {
+        HaxelibType::Git => Some(format!(
+            "git:{}#{}",
+            lib.url.as_deref().unwrap_or(""),
+            lib.vcs_ref.as_deref().unwrap_or("")
+        )),
+        _ => lib.version.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lib() {
+        let lib = parse_lib("lime").unwrap();
+        assert_eq!(lib.name, "lime");
+        assert_eq!(lib.version, None);
+
+        let lib = parse_lib("lime:8.1.0").unwrap();
+        assert_eq!(lib.haxelib_type, HaxelibType::Haxelib);
+        assert_eq!(lib.version.as_deref(), Some("8.1.0"));
+
+        let lib = parse_lib("flixel:git:https://github.com/haxeflixel/flixel#dev").unwrap();
+        assert_eq!(lib.haxelib_type, HaxelibType::Git);
+        assert_eq!(
+            lib.url.as_deref(),
+            Some("https://github.com/haxeflixel/flixel")
+        );
+        assert_eq!(lib.vcs_ref.as_deref(), Some("dev"));
+
+        let lib = parse_lib("native:git:git@github.com:org/native.git#v1").unwrap();
+        assert_eq!(lib.url.as_deref(), Some("git@github.com:org/native.git"));
+        assert_eq!(lib.vcs_ref.as_deref(), Some("v1"));
+
+        assert!(parse_lib(":8.1.0").is_err());
+    }
+
+    #[test]
+    fn test_parse_hxml_sections() {
+        let hxml = r#"
+# comment with -lib not-a-lib
+-cp src
+-lib lime
+--library "openfl:9.3.3"
+-main Main
+
+--next
+-lib lime:8.1.0
+-L flixel:git:https://github.com/haxeflixel/flixel
+--macro "include('not-an-include.hxml')"
+-resource not-an-include.hxml@data
+--run Main not-an-include.hxml
+"#;
+        let mut libs = HxmlLibs::default();
+        libs.parse_str(hxml, Path::new("")).unwrap();
+
+        let names: Vec<&str> = libs.libs.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["lime", "openfl", "flixel"]);
+        assert_eq!(libs.libs[0].version.as_deref(), Some("8.1.0"));
+        assert_eq!(libs.libs[1].version.as_deref(), Some("9.3.3"));
+        assert!(libs.conflicts.is_empty());
+        assert!(libs.split_builds);
+    }
+
+    #[test]
+    fn test_parse_hxml_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("common.hxml"), "-lib lime:8.0.0\n").unwrap();
+        // includes are relative to the working directory, which `--cwd` moves
+        std::fs::write(
+            dir.path().join("build.hxml"),
+            format!(
+                "--cwd {}\ncommon.hxml\nbuild.hxml\n-lib lime:8.1.0\n-lib format\n",
+                dir.path().display()
+            ),
+        )
+        .unwrap();
+
+        let mut libs = HxmlLibs::default();
+        libs.parse_file(&dir.path().join("build.hxml")).unwrap();
+
+        assert_eq!(libs.libs.len(), 2);
+        assert_eq!(libs.libs[0].version.as_deref(), Some("8.0.0"));
+        assert_eq!(
+            libs.conflicts,
+            vec![("lime".to_string(), "8.0.0".to_string(), "8.1.0".to_string())]
+        );
+    }
+}
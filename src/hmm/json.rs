@@ -16,6 +16,7 @@ pub fn save_json(deps: Dependancies, path: PathBuf) -> Result<()> {
 pub fn create_empty_hmm_json() -> Result<()> {
     let empty_deps = Dependancies {
         dependencies: vec![],
+        registries: vec![],
     };
 
     save_json(empty_deps, PathBuf::from_str("hmm.json")?)
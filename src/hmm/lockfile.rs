@@ -0,0 +1,176 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::dependencies::Dependancies;
+use super::haxelib::{Haxelib, HaxelibType};
+use super::registry;
+use super::resolver;
+
+/// `hmm-lock.json`, the exact versions everything got installed at.
+/// Unlike hmm.json it's never edited by hand, install and update rewrite it
+#[derive(Serialize, Deserialize, Default)]
+pub struct Lockfile {
+    pub dependencies: Vec<LockedDependency>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LockedDependency {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub haxelib_type: HaxelibType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The zip download for haxelibs, or the repository for git dependencies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// SHA-256 of the haxelib zip
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+}
+
+/// Where a haxelib zip came from, kept next to the extracted files so the lockfile can record it
+#[derive(Serialize, Deserialize)]
+pub struct DownloadRecord {
+    pub url: String,
+    pub sha256: String,
+}
+
+const DOWNLOAD_RECORD: &str = ".hmm-download.json";
+
+impl DownloadRecord {
+    pub fn read(version_dir: &Path) -> Option<DownloadRecord> {
+        let file = File::open(version_dir.join(DOWNLOAD_RECORD)).ok()?;
+        serde_json::from_reader(file).ok()
+    }
+
+    pub fn write(&self, version_dir: &Path) -> Result<()> {
+        let mut file = File::create(version_dir.join(DOWNLOAD_RECORD))?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// The lockfile lives next to hmm.json
+pub fn lockfile_path(json_path: &Path) -> PathBuf {
+    json_path.with_file_name("hmm-lock.json")
+}
+
+impl Lockfile {
+    pub fn read(path: &Path) -> Result<Lockfile> {
+        let file = File::open(path).context(format!(
+            "{} not found, run `hmm-rs install` to create it",
+            path.display()
+        ))?;
+        let lockfile =
+            serde_json::from_reader(file).context(format!("Error parsing {}", path.display()))?;
+        Ok(lockfile)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        println!("{} saved/updated", path.display());
+        let j = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(j.as_bytes())?;
+        Ok(())
+    }
+
+    /// Records what's currently installed for hmm.json, and everything its dependencies require
+    pub fn from_installed(deps: &Dependancies, json_path: &Path) -> Result<Lockfile> {
+        let mut lockfile = Lockfile::default();
+        for haxelib in deps.dependencies.iter() {
+            lockfile.dependencies.push(lock_installed(haxelib)?);
+        }
+
+        let graph = resolver::resolve(deps, json_path)?;
+        for transitive in graph.transitive.iter() {
+            lockfile
+                .dependencies
+                .push(lock_installed(&transitive.haxelib)?);
+        }
+
+        Ok(lockfile)
+    }
+}
+
+/// Writes the lockfile for everything installed from `json_path`
+pub fn write_lockfile(deps: &Dependancies, json_path: &Path) -> Result<()> {
+    Lockfile::from_installed(deps, json_path)?.save(&lockfile_path(json_path))
+}
+
+fn lock_installed(haxelib: &Haxelib) -> Result<LockedDependency> {
+    let mut locked = LockedDependency {
+        name: haxelib.name.clone(),
+        haxelib_type: haxelib.haxelib_type.clone(),
+        version: None,
+        url: None,
+        commit: None,
+        sha256: None,
+        dir: None,
+    };
+
+    let lib_path = haxelib.lib_path();
+    match haxelib.haxelib_type {
+        HaxelibType::Haxelib => {
+            let version = std::fs::read_to_string(lib_path.join(".current"))
+                .context(format!("{} is not installed", haxelib.name))?;
+            let version = version.trim().to_string();
+
+            let version_dir = lib_path.join(version.replace(".", ","));
+            match DownloadRecord::read(&version_dir) {
+                Some(record) => {
+                    locked.url = Some(record.url);
+                    locked.sha256 = Some(record.sha256);
+                }
+                None => {
+                    let registry = &registry::registries()[0];
+                    locked.url = Some(registry.archive_url(&haxelib.name, &version));
+                }
+            }
+            locked.version = Some(version);
+        }
+        HaxelibType::Git => {
+            let repo = gix::open(lib_path.join("git"))
+                .context(format!("{} is not cloned", haxelib.name))?;
+            locked.commit = Some(repo.head_id()?.to_string());
+            locked.url = haxelib.url.clone();
+            locked.dir = haxelib.dir.clone();
+        }
+        HaxelibType::Dev => locked.dir = haxelib.dir.clone(),
+        HaxelibType::Mecurial => {
+            return Err(anyhow!(
+                "{}: locking {:?} dependencies is not supported",
+                haxelib.name,
+                haxelib.haxelib_type
+            ))
+        }
+    }
+
+    Ok(locked)
+}
+
+impl LockedDependency {
+    /// The hmm.json style entry that installs exactly this locked version
+    pub fn to_haxelib(&self) -> Haxelib {
+        Haxelib {
+            name: self.name.clone(),
+            haxelib_type: self.haxelib_type.clone(),
+            vcs_ref: self.commit.clone(),
+            dir: self.dir.clone(),
+            url: match self.haxelib_type {
+                HaxelibType::Git => self.url.clone(),
+                _ => None,
+            },
+            version: self.version.clone(),
+            overrides: false,
+            shallow: None,
+        }
+    }
+}
@@ -1,3 +1,11 @@
+pub mod cache;
+pub mod config;
 pub mod dependencies;
+pub mod haxe_serialization;
 pub mod haxelib;
+pub mod haxelib_json;
+pub mod hxml;
 pub mod json;
+pub mod lockfile;
+pub mod registry;
+pub mod resolver;
@@ -0,0 +1,442 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::blocking::Client;
+use yansi::Paint;
+use zip::ZipArchive;
+
+use super::config;
+use super::haxe_serialization::{self, Value};
+use super::haxelib_json::HaxelibJson;
+
+/// Used when neither hmm.json nor the user config list any registries
+pub const DEFAULT_REGISTRY: &str = "https://lib.haxe.org";
+
+/// What a registry knows about a library
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub website: Option<String>,
+    pub license: Option<String>,
+    pub latest_version: Option<String>,
+    /// Every published version, oldest first
+    pub versions: Vec<String>,
+}
+
+/// Somewhere haxelibs can be installed from
+pub trait Registry {
+    /// A short name for output
+    fn name(&self) -> String;
+
+    /// Whether the registry works without a network connection
+    fn is_local(&self) -> bool;
+
+    fn latest_version(&self, name: &str) -> Result<String>;
+
+    /// Every published version of a library, oldest first
+    fn versions(&self, name: &str) -> Result<Vec<String>>;
+
+    fn info(&self, name: &str) -> Result<LibraryInfo>;
+
+    /// Where the zip of a version lives, what gets recorded in hmm-lock.json
+    fn archive_url(&self, name: &str, version: &str) -> String;
+
+    /// Saves the zip of a version to `path`
+    fn fetch_archive(&self, name: &str, version: &str, path: &Path) -> Result<()>;
+}
+
+/// Every registry to try, in order. hmm.json's come first, then the user config's,
+/// and lib.haxe.org only when neither lists any
+pub fn registries() -> Vec<Box<dyn Registry>> {
+    let mut locations = config::settings().registries;
+    if locations.is_empty() {
+        locations.push(DEFAULT_REGISTRY.to_string());
+    }
+    locations.iter().map(|location| open(location)).collect()
+}
+
+/// An http(s) url is a lib.haxe.org compatible server, anything else a folder of zips
+pub fn open(location: &str) -> Box<dyn Registry> {
+    if location.starts_with("http://") || location.starts_with("https://") {
+        return Box::new(HttpRegistry::new(location));
+    }
+    let path = location.strip_prefix("file://").unwrap_or(location);
+    Box::new(FileRegistry::new(PathBuf::from(path)))
+}
+
+/// hmm.json's folder registries are relative to the folder hmm.json is in, not the working directory
+pub fn relative_to_json(registries: &[String], json_path: &Path) -> Vec<String> {
+    let json_dir = json_path.parent().unwrap_or(Path::new(""));
+    registries
+        .iter()
+        .map(|location| {
+            if location.starts_with("http://") || location.starts_with("https://") {
+                return location.clone();
+            }
+            let path = location.strip_prefix("file://").unwrap_or(location);
+            json_dir.join(path).to_string_lossy().to_string()
+        })
+        .collect()
+}
+
+/// Merges the registries from hmm.json and the user config, dropping duplicates
+pub fn merge(project: &[String], user: &[String]) -> Vec<String> {
+    let mut merged: Vec<String> = Vec::new();
+    for registry in project.iter().chain(user.iter()) {
+        let registry = registry.trim_end_matches('/').to_string();
+        if !merged.contains(&registry) {
+            merged.push(registry);
+        }
+    }
+    merged
+}
+
+/// A server speaking the same api as lib.haxe.org
+pub struct HttpRegistry {
+    base_url: String,
+}
+
+impl HttpRegistry {
+    pub fn new(base_url: &str) -> HttpRegistry {
+        HttpRegistry {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Calls a function of the haxe remoting api, what the haxelib client talks to
+    fn call(&self, function: &str, args: &[&str]) -> Result<Value> {
+        config::ensure_online(&format!("ask {} about {}", self.name(), args.join(" ")))?;
+
+        // haxelib url: lib.haxe.org/api/3.0/index.n/
+        // needs X-Haxe-Remoting header
+        // and __x param with the query
+        // in __x param, we can query with something like
+        // ay3:apiy16:getLatestVersionhay4:limeh
+        let serialized = format!(
+            "{}{}",
+            haxe_serialization::serialize_strings(&["api", function]),
+            haxe_serialization::serialize_strings(args)
+        );
+
+        let resp = Client::new()
+            .get(format!("{}/api/3.0/index.n/", self.base_url))
+            .header("X-Haxe-Remoting", "1")
+            .query(&[("__x", serialized)])
+            .send()?
+            .error_for_status()?
+            .text()?;
+
+        let reply = resp.strip_prefix("hxr").ok_or(anyhow!(
+            "Unexpected response from {}: {}",
+            self.name(),
+            resp
+        ))?;
+        match reply.strip_prefix("x") {
+            // the server threw, like "No such Project" for a haxelib that doesn't exist
+            Some(exception) => match haxe_serialization::unserialize(exception)? {
+                Value::String(message) => Err(anyhow!("{}", message)),
+                other => Err(anyhow!("{:?}", other)),
+            },
+            None => haxe_serialization::unserialize(reply),
+        }
+    }
+}
+
+impl Registry for HttpRegistry {
+    fn name(&self) -> String {
+        let without_scheme = self
+            .base_url
+            .split_once("://")
+            .map_or(self.base_url.as_str(), |(_, rest)| rest);
+        without_scheme
+            .split('/')
+            .next()
+            .unwrap_or(without_scheme)
+            .to_string()
+    }
+
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    fn latest_version(&self, name: &str) -> Result<String> {
+        match self.call("getLatestVersion", &[name])? {
+            Value::String(version) => Ok(version),
+            other => Err(anyhow!(
+                "Unexpected latest version of {}: {:?}",
+                name,
+                other
+            )),
+        }
+    }
+
+    fn versions(&self, name: &str) -> Result<Vec<String>> {
+        Ok(self.info(name)?.versions)
+    }
+
+    fn info(&self, name: &str) -> Result<LibraryInfo> {
+        let infos = self.call("infos", &[name])?;
+        let field = |field: &str| infos.get(field).and_then(Value::as_str).map(str::to_string);
+
+        // lib.haxe.org lists the newest version first
+        let mut versions: Vec<String> = infos
+            .get("versions")
+            .and_then(Value::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|version| version.get("name").and_then(Value::as_str))
+            .map(str::to_string)
+            .collect();
+        sort_versions(&mut versions);
+
+        Ok(LibraryInfo {
+            name: field("name").unwrap_or(name.to_string()),
+            description: field("desc"),
+            website: field("website"),
+            license: field("license"),
+            latest_version: field("curversion"),
+            versions,
+        })
+    }
+
+    fn archive_url(&self, name: &str, version: &str) -> String {
+        format!("{}/p/{}/{}/download", self.base_url, name, version)
+    }
+
+    fn fetch_archive(&self, name: &str, version: &str, path: &Path) -> Result<()> {
+        config::ensure_online(&format!("download {} {}", name, version))?;
+        let target_url = self.archive_url(name, version);
+        println!(
+            "Downloading: {} - {} - {}",
+            name.bold(),
+            self.name().yellow().bold(),
+            target_url.bold()
+        );
+
+        let response = Client::new().get(&target_url).send()?.error_for_status()?;
+        let total_size = response.content_length().unwrap_or_default();
+        // yoinked from haxeget !
+        let pb = ProgressBar::new(total_size);
+        pb.set_style(ProgressStyle::with_template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.yellow/red}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+                 .unwrap());
+
+        let mut file = File::create(path)?;
+        std::io::copy(&mut pb.wrap_read(response), &mut file)?;
+
+        let finish_message = format!(
+            "{}: {} done downloading from {}",
+            name.green().bold(),
+            version.bright_green(),
+            self.name().yellow().bold()
+        );
+        pb.finish_with_message(finish_message);
+        Ok(())
+    }
+}
+
+/// A folder of zips laid out as `<name>/<version>.zip`, for air-gapped machines and tests
+pub struct FileRegistry {
+    root: PathBuf,
+}
+
+impl FileRegistry {
+    pub fn new(root: PathBuf) -> FileRegistry {
+        FileRegistry { root }
+    }
+
+    fn archive_path(&self, name: &str, version: &str) -> PathBuf {
+        self.root.join(name).join(format!("{}.zip", version))
+    }
+
+    /// The haxelib.json inside of a zip, which might be inside of a top level folder
+    fn read_haxelib_json(&self, name: &str, version: &str) -> Result<HaxelibJson> {
+        let zip_path = self.archive_path(name, version);
+        let mut zip = ZipArchive::new(File::open(&zip_path)?)
+            .context(format!("Error opening {}", zip_path.display()))?;
+
+        let json_name = zip
+            .file_names()
+            .filter(|file| *file == "haxelib.json" || file.ends_with("/haxelib.json"))
+            .min_by_key(|file| file.len())
+            .ok_or(anyhow!("{} has no haxelib.json", zip_path.display()))?
+            .to_string();
+        let haxelib_json = serde_json::from_reader(zip.by_name(&json_name)?).context(format!(
+            "Error parsing haxelib.json in {}",
+            zip_path.display()
+        ))?;
+        Ok(haxelib_json)
+    }
+}
+
+impl Registry for FileRegistry {
+    fn name(&self) -> String {
+        self.root.display().to_string()
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    fn latest_version(&self, name: &str) -> Result<String> {
+        self.versions(name)?
+            .pop()
+            .ok_or(anyhow!("No versions of {} in {}", name, self.name()))
+    }
+
+    fn versions(&self, name: &str) -> Result<Vec<String>> {
+        let lib_dir = self.root.join(name);
+        let entries = std::fs::read_dir(&lib_dir).context(format!(
+            "No such Project: {} isn't in {}",
+            name,
+            self.name()
+        ))?;
+
+        let mut versions: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "zip"))
+            .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+            .collect();
+        sort_versions(&mut versions);
+        Ok(versions)
+    }
+
+    fn info(&self, name: &str) -> Result<LibraryInfo> {
+        let versions = self.versions(name)?;
+        let latest_version = versions.last().cloned();
+        let haxelib_json = match &latest_version {
+            Some(version) => Some(self.read_haxelib_json(name, version)?),
+            None => None,
+        };
+
+        Ok(LibraryInfo {
+            name: name.to_string(),
+            description: haxelib_json.as_ref().and_then(|j| j.description.clone()),
+            website: haxelib_json.as_ref().and_then(|j| j.url.clone()),
+            license: haxelib_json.as_ref().and_then(|j| j.license.clone()),
+            latest_version,
+            versions,
+        })
+    }
+
+    fn archive_url(&self, name: &str, version: &str) -> String {
+        let path = self.archive_path(name, version);
+        let path = std::path::absolute(&path).unwrap_or(path);
+        format!("file://{}", path.display())
+    }
+
+    fn fetch_archive(&self, name: &str, version: &str, path: &Path) -> Result<()> {
+        let archive = self.archive_path(name, version);
+        println!(
+            "Copying: {} - {} - {}",
+            name.bold(),
+            self.name().yellow().bold(),
+            archive.display().bold()
+        );
+        std::fs::copy(&archive, path).context(format!("{} not found", archive.display()))?;
+        Ok(())
+    }
+}
+
+/// Sorts oldest to newest, by semver when the versions are semver
+fn sort_versions(versions: &mut [String]) {
+    versions.sort_by(
+        |a, b| match (semver::Version::parse(a), semver::Version::parse(b)) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => a.cmp(b),
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_registry() {
+        let registry = open("https://lib.haxe.org/");
+        assert_eq!(registry.name(), "lib.haxe.org");
+        assert!(!registry.is_local());
+        assert_eq!(
+            registry.archive_url("lime", "8.1.0"),
+            "https://lib.haxe.org/p/lime/8.1.0/download"
+        );
+    }
+
+    #[test]
+    fn test_file_registry() {
+        let dir = tempfile::tempdir().unwrap();
+        let lime_dir = dir.path().join("lime");
+        std::fs::create_dir_all(&lime_dir).unwrap();
+        for version in ["8.0.2", "10.0.0", "8.1.0"] {
+            let mut zip = zip::ZipWriter::new(
+                File::create(lime_dir.join(format!("{}.zip", version))).unwrap(),
+            );
+            zip.start_file(
+                "lime/haxelib.json",
+                zip::write::SimpleFileOptions::default(),
+            )
+            .unwrap();
+            let json = format!(
+                "{{\"name\": \"lime\", \"version\": \"{}\", \"license\": \"MIT\"}}",
+                version
+            );
+            std::io::Write::write_all(&mut zip, json.as_bytes()).unwrap();
+            zip.finish().unwrap();
+        }
+
+        let registry = open(&format!("file://{}", dir.path().display()));
+        assert!(registry.is_local());
+        assert_eq!(
+            registry.versions("lime").unwrap(),
+            vec!["8.0.2", "8.1.0", "10.0.0"]
+        );
+        assert_eq!(registry.latest_version("lime").unwrap(), "10.0.0");
+        assert_eq!(
+            registry.info("lime").unwrap().license.as_deref(),
+            Some("MIT")
+        );
+        assert!(registry.latest_version("openfl").is_err());
+
+        let download = dir.path().join("download.zip");
+        registry.fetch_archive("lime", "8.1.0", &download).unwrap();
+        assert!(download.exists());
+        assert!(registry.fetch_archive("lime", "1.0.0", &download).is_err());
+    }
+
+    #[test]
+    fn test_relative_to_json() {
+        let registries = vec![
+            "https://mirror.internal".to_string(),
+            "file://vendor/zips".to_string(),
+            "/opt/zips".to_string(),
+        ];
+        assert_eq!(
+            relative_to_json(&registries, Path::new("/project/hmm.json")),
+            vec![
+                "https://mirror.internal".to_string(),
+                Path::new("/project")
+                    .join("vendor/zips")
+                    .to_string_lossy()
+                    .to_string(),
+                "/opt/zips".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let project = vec!["https://mirror.internal/".to_string()];
+        let user = vec![
+            "https://mirror.internal".to_string(),
+            DEFAULT_REGISTRY.to_string(),
+        ];
+        assert_eq!(
+            merge(&project, &user),
+            vec!["https://mirror.internal", DEFAULT_REGISTRY]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use super::dependencies::Dependancies;
+use super::haxelib::{Haxelib, HaxelibType};
+use super::haxelib_json::HaxelibJson;
+use super::hxml::parse_lib;
+
+/// A library asking for another library in its haxelib.json
+#[derive(Debug, Clone, PartialEq)]
+pub struct Requirement {
+    pub required_by: String,
+    /// The version asked for, an empty string means any version
+    pub version: String,
+}
+
+/// A library that isn't in hmm.json, but is needed by something that is
+pub struct TransitiveDependency {
+    /// What would get installed for this library
+    pub haxelib: Haxelib,
+    pub required_by: Vec<Requirement>,
+    pub installed: bool,
+}
+
+/// A library that hmm.json and the libraries requiring it don't agree on the version of
+pub struct Conflict {
+    pub name: String,
+    /// The version hmm.json pins, if the library is in hmm.json
+    pub pinned: Option<String>,
+    /// Every requirement that asks for a specific version
+    pub requirements: Vec<Requirement>,
+    /// hmm.json has `"override": true` for this library, so its version wins
+    pub overridden: bool,
+}
+
+#[derive(Default)]
+pub struct DependencyGraph {
+    /// Every requirement found in the installed haxelib.json files, keyed by the required library
+    pub requirements: BTreeMap<String, Vec<Requirement>>,
+    /// Libraries that are only required by other libraries, in the order they were found
+    pub transitive: Vec<TransitiveDependency>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl DependencyGraph {
+    pub fn missing(&self) -> impl Iterator<Item = &TransitiveDependency> {
+        self.transitive.iter().filter(|t| !t.installed)
+    }
+
+    /// Conflicts that hmm.json doesn't explicitly override
+    pub fn unresolved_conflicts(&self) -> impl Iterator<Item = &Conflict> {
+        self.conflicts.iter().filter(|c| !c.overridden)
+    }
+}
+
+/// Walks the haxelib.json files of everything installed, starting from the hmm.json dependencies
+pub fn resolve(deps: &Dependancies, json_path: &Path) -> Result<DependencyGraph> {
+    let mut graph = DependencyGraph::default();
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut queue: VecDeque<Haxelib> = deps.dependencies.iter().cloned().collect();
+
+    while let Some(haxelib) = queue.pop_front() {
+        if !visited.insert(haxelib.name.clone()) {
+            continue;
+        }
+
+        // not installed yet, so there's no haxelib.json to read
+        let haxelib_json = match installed_lib_dir(&haxelib, json_path) {
+            Some(lib_dir) => HaxelibJson::read(&lib_dir)
+                .context(format!("{}'s haxelib.json couldn't be read", haxelib.name))?,
+            None => continue,
+        };
+
+        for (name, version) in haxelib_json.dependencies {
+            let requirement = Requirement {
+                required_by: haxelib.name.clone(),
+                version: version.clone(),
+            };
+            graph
+                .requirements
+                .entry(name.clone())
+                .or_default()
+                .push(requirement.clone());
+
+            if deps.get_haxelib(&name).is_ok() {
+                continue;
+            }
+
+            match graph.transitive.iter_mut().find(|t| t.haxelib.name == name) {
+                Some(transitive) => {
+                    transitive.required_by.push(requirement);
+                    // the first requirement to ask for a specific version wins
+                    if transitive.haxelib.version.is_none() && !version.is_empty() {
+                        transitive.haxelib = requirement_to_haxelib(&name, &version)?;
+                        transitive.installed = is_installed(&transitive.haxelib);
+                    }
+                }
+                None => {
+                    let transitive_lib = requirement_to_haxelib(&name, &version)?;
+                    let installed = is_installed(&transitive_lib);
+                    queue.push_back(transitive_lib.clone());
+                    graph.transitive.push(TransitiveDependency {
+                        haxelib: transitive_lib,
+                        required_by: vec![requirement],
+                        installed,
+                    });
+                }
+            }
+        }
+    }
+
+    graph.conflicts = find_conflicts(deps, &graph.requirements);
+    Ok(graph)
+}
+
+fn find_conflicts(
+    deps: &Dependancies,
+    requirements: &BTreeMap<String, Vec<Requirement>>,
+) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+
+    for (name, requirements) in requirements.iter() {
+        let direct = deps.get_haxelib(name).ok();
+        let pinned = match direct {
+            // a git or dev dependency in hmm.json already replaces whatever version is required
+            Some(haxelib) if haxelib.haxelib_type != HaxelibType::Haxelib => continue,
+            Some(haxelib) => haxelib.version.clone(),
+            None => None,
+        };
+
+        // "any version" and git sources can't disagree with a version number
+        let versioned: Vec<Requirement> = requirements
+            .iter()
+            .filter(|r| !r.version.is_empty() && !r.version.starts_with("git:"))
+            .cloned()
+            .collect();
+
+        let mut versions: BTreeSet<&str> = versioned.iter().map(|r| r.version.as_str()).collect();
+        if let Some(pinned) = &pinned {
+            versions.insert(pinned);
+        }
+        if versions.len() < 2 {
+            continue;
+        }
+
+        conflicts.push(Conflict {
+            name: name.clone(),
+            pinned,
+            requirements: versioned,
+            overridden: direct.is_some_and(|haxelib| haxelib.overrides),
+        });
+    }
+
+    conflicts
+}
+
+/// The folder an installed library keeps its haxelib.json in
+pub fn installed_lib_dir(haxelib: &Haxelib, json_path: &Path) -> Option<PathBuf> {
+    let lib_path = haxelib.lib_path();
+
+    let lib_dir = match haxelib.haxelib_type {
+        // whatever version is actually installed is the one whose requirements matter
+        HaxelibType::Haxelib => {
+            let version = match std::fs::read_to_string(lib_path.join(".current")) {
+                Ok(current) => current,
+                Err(_) => haxelib.version.clone()?,
+            };
+            lib_path.join(version.trim().replace(".", ","))
+        }
+        HaxelibType::Git => match &haxelib.dir {
+            Some(dir) => lib_path.join("git").join(dir),
+            None => lib_path.join("git"),
+        },
+        HaxelibType::Dev => haxelib.dev_path(json_path)?,
+        HaxelibType::Mecurial => return None,
+    };
+
+    if lib_dir.join("haxelib.json").exists() {
+        return Some(lib_dir);
+    }
+
+    // some haxelib zips have everything inside of a single top level folder
+    std::fs::read_dir(&lib_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.join("haxelib.json").exists())
+}
+
+/// haxelib.json dependencies are either a version, an empty string for any version,
+/// or a git source like `git:https://github.com/user/repo#ref`
+fn requirement_to_haxelib(name: &str, version: &str) -> Result<Haxelib> {
+    match version.is_empty() {
+        true => parse_lib(name),
+        false => parse_lib(&format!("{}:{}", name, version)),
+    }
+}
+
+/// Whether .haxelib/ has the version `haxelib` asks for, compared the same way `hmm-rs check` does
+fn is_installed(haxelib: &Haxelib) -> bool {
+    let lib_path = haxelib.lib_path();
+    match haxelib.haxelib_type {
+        HaxelibType::Git => {
+            let repo = match gix::open(lib_path.join("git")) {
+                Ok(repo) => repo,
+                Err(_) => return false,
+            };
+            let vcs_ref = match &haxelib.vcs_ref {
+                Some(vcs_ref) => vcs_ref,
+                None => return true,
+            };
+            let remote_ref = format!("origin/{}", vcs_ref);
+            let wants = [vcs_ref.as_str(), remote_ref.as_str()]
+                .into_iter()
+                .find_map(|name| repo.rev_parse_single(name).ok())
+                .and_then(|id| id.object().ok()?.peel_to_commit().ok())
+                .map(|commit| commit.id);
+            wants.is_some() && wants == repo.head_id().ok().map(|id| id.detach())
+        }
+        _ => {
+            let current_file = match lib_path.join(".dev").exists() {
+                true => lib_path.join(".dev"),
+                false => lib_path.join(".current"),
+            };
+            match std::fs::read_to_string(current_file) {
+                Ok(current) => haxelib
+                    .version
+                    .as_ref()
+                    .is_none_or(|version| version == current.trim()),
+                Err(_) => false,
+            }
+        }
+    }
+}
@@ -20,6 +20,21 @@ struct Cli {
     /// Sets a custom hmm.json file to use
     #[arg(short, long, value_name = "JSON", default_value = "hmm.json")]
     json: Option<PathBuf>,
+
+    /// Downloads everything again, without reading from or saving to the download cache
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// Never touches lib.haxe.org or git remotes, only installing from the caches.
+    /// Can also be turned on with `"offline": true` in the user config file
+    #[arg(long, global = true)]
+    offline: bool,
+
+    /// Clones git dependencies at depth 1 with no tags, fetching more history only when needed.
+    /// Can also be turned on with `"shallow": true` in the user config file,
+    /// or per dependency in hmm.json
+    #[arg(long, global = true)]
+    shallow: bool,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -44,32 +59,148 @@ enum Commands {
         #[arg(value_name = "HXML")]
         hxml: Option<PathBuf>,
     },
-    /// Checks if the dependencies are installed at their correct hmm.json versions
+    /// Adds the libraries used by one or more .hxml files to hmm.json
+    FromHxml {
+        /// The hxml file(s) to read, included .hxml files are followed too
+        #[arg(value_name = "HXML", required = true)]
+        hxml: Vec<PathBuf>,
+    },
+    /// Checks if the dependencies are installed at their correct hmm.json versions.
+    /// Exits with an error if any aren't
     #[command(visible_alias = "ch")]
-    Check,
+    Check {
+        /// How to print the statuses
+        #[arg(long, value_enum, default_value_t = commands::OutputFormat::Text)]
+        format: commands::OutputFormat,
+    },
+    /// Lists dependencies that have a newer version available, without changing anything.
+    /// Exits with an error if any are outdated
+    Outdated {
+        /// How to print the report
+        #[arg(long, value_enum, default_value_t = commands::OutputFormat::Text)]
+        format: commands::OutputFormat,
+    },
     /// Installs the dependencies from hmm.json, if they aren't already installed.
     #[command(visible_alias = "i")]
-    Install,
+    Install {
+        /// Installs exactly the versions in hmm-lock.json, instead of resolving hmm.json
+        #[arg(long)]
+        locked: bool,
+        /// Errors on branches, `"version": null`, or anything that would change hmm-lock.json.
+        /// Meant for CI
+        #[arg(long, conflicts_with = "locked")]
+        frozen: bool,
+    },
+    /// Reinstalls the dependencies from hmm.json, even if they're already installed.
+    /// Useful when something in .haxelib/ got corrupted or modified
+    #[command(visible_alias = "ri")]
+    Reinstall {
+        /// Specific libraries you want to reinstall, can be multiple
+        #[arg(value_name = "LIBS")]
+        lib: Option<Vec<String>>,
+    },
+    /// Updates dependencies to their newest versions, and saves them to hmm.json
+    /// haxelibs move to the latest version on lib.haxe.org,
+    /// and git dependencies on a branch move to the newest commit on that branch
+    #[command(visible_alias = "up")]
+    Update {
+        /// Specific libraries you want to update, can be multiple
+        #[arg(value_name = "LIBS")]
+        lib: Option<Vec<String>>,
+        /// Only shows what would be updated, without installing anything or changing hmm.json
+        #[arg(long)]
+        dry_run: bool,
+    },
     /// Installs a haxelib from lib.haxe.org
     Haxelib {
         /// The name of the haxelib to install
         name: String,
         /// The version of the haxelib to install
         version: Option<String>,
+        /// Replaces an existing git or dev dependency of the same name without asking
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Installs a library from a git repository, adding it to hmm.json
+    Git {
+        /// The name of the library
+        name: String,
+        /// The url of the git repository
+        url: String,
+        /// The branch, tag or commit to check out, defaults to the remote's default branch
+        #[arg(value_name = "REF")]
+        vcs_ref: Option<String>,
+        /// Pins the dependency to the commit that got checked out
+        #[arg(long)]
+        lock: bool,
+    },
+    /// Uses a local folder for a library, adding it to hmm.json as a dev dependency
+    Dev {
+        /// The name of the library
+        name: String,
+        /// The folder containing the library's haxelib.json
+        path: PathBuf,
+    },
+    /// Locks dependencies in hmm.json to the versions currently installed
+    /// haxelibs with `"version": null` are pinned to their installed version,
+    /// and git dependencies on a branch or tag are pinned to a commit
+    Lock {
+        /// Specific libraries you want to lock, can be multiple
+        /// `hmm-rs lock lime openfl` will only lock lime and openfl
+        #[arg(value_name = "LIBS")]
+        lib: Option<Vec<String>>,
     },
     /// Removes one or more library dependencies from `hmm.json` and the `.haxelib/` folder
     #[command(visible_alias = "rm")]
     Remove {
         /// The library(s) you wish to remove, can be multiple
-        #[arg(value_name = "LIBS")]
+        #[arg(value_name = "LIBS", required = true)]
         lib: Vec<String>,
+
+        /// Only edit hmm.json, leaving the installed files in `.haxelib/` alone
+        #[arg(long)]
+        keep_files: bool,
+    },
+    /// Manages the per-user download cache, shared between every project
+    Cache {
+        #[command(subcommand)]
+        cmd: CacheCommands,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum CacheCommands {
+    /// Lists the haxelib zips and git repos in the cache
+    #[command(visible_alias = "ls")]
+    List,
+    /// Deletes everything in the cache
+    Clear,
+    /// Deletes zips and git repos that haven't been installed from in a while
+    Prune {
+        /// How many days something can go unused before it's deleted
+        #[arg(long, default_value_t = 30)]
+        days: u64,
     },
 }
 
 pub fn run() -> Result<()> {
     let args = Cli::parse();
-
     let path = args.json.unwrap();
+
+    let user_config = hmm::config::UserConfig::load()?;
+    // a broken or missing hmm.json gets reported by whichever command needs it
+    let project_registries = match path.exists() {
+        true => hmm::json::read_json(&path)
+            .map(|deps| hmm::registry::relative_to_json(&deps.registries, &path))
+            .unwrap_or_default(),
+        false => vec![],
+    };
+    hmm::config::set(hmm::config::Settings {
+        no_cache: args.no_cache,
+        offline: args.offline || user_config.offline,
+        registries: hmm::registry::merge(&project_registries, &user_config.registries),
+        shallow: args.shallow || user_config.shallow,
+    });
     let load_deps = || hmm::json::read_json(&path);
 
     match args.cmd {
@@ -77,12 +208,49 @@ pub fn run() -> Result<()> {
         Commands::Init => commands::init_command::init_hmm()?,
         Commands::Clean => commands::clean_command::remove_haxelib_folder()?,
         Commands::ToHxml { hxml } => commands::tohxml_command::dump_to_hxml(&load_deps()?, hxml)?,
-        Commands::Check => commands::check_command::check(&load_deps()?)?,
-        Commands::Install => commands::install_command::install_from_hmm(&load_deps()?)?,
-        Commands::Haxelib { name, version } => {
-            commands::haxelib_command::install_haxelib(&name, &version, load_deps()?, path)?
+        Commands::FromHxml { hxml } => commands::fromhxml_command::import_from_hxml(&hxml, path)?,
+        Commands::Check { format } => commands::check_command::check(&load_deps()?, &path, format)?,
+        Commands::Outdated { format } => {
+            commands::outdated_command::report_outdated(&load_deps()?, &path, format)?
+        }
+        Commands::Install { locked, frozen } => match locked {
+            true => commands::install_command::install_from_lockfile(&path)?,
+            false => commands::install_command::install_from_hmm(&load_deps()?, &path, frozen)?,
+        },
+        Commands::Reinstall { lib } => {
+            commands::reinstall_command::reinstall_from_hmm(&load_deps()?, &lib, &path)?
+        }
+        Commands::Update { lib, dry_run } => {
+            commands::update_command::update_dependencies(&lib, dry_run, load_deps()?, path)?
+        }
+        Commands::Haxelib {
+            name,
+            version,
+            force,
+        } => {
+            commands::haxelib_command::install_haxelib(&name, &version, force, load_deps()?, path)?
+        }
+        Commands::Git {
+            name,
+            url,
+            vcs_ref,
+            lock,
+        } => commands::git_command::install_git(&name, &url, &vcs_ref, lock, load_deps()?, path)?,
+        Commands::Dev {
+            name,
+            path: dev_path,
+        } => commands::dev_command::install_dev(&name, &dev_path, load_deps()?, path)?,
+        Commands::Lock { lib } => {
+            commands::lock_command::lock_dependencies(&lib, load_deps()?, path)?
+        }
+        Commands::Remove { lib, keep_files } => {
+            commands::remove_command::remove_haxelibs(&lib, keep_files, load_deps()?, path)?
         }
-        Commands::Remove { lib: _ } => commands::remove_command::remove_haxelibs()?,
+        Commands::Cache { cmd } => match cmd {
+            CacheCommands::List => commands::cache_command::list_cache()?,
+            CacheCommands::Clear => commands::cache_command::clear_cache()?,
+            CacheCommands::Prune { days } => commands::cache_command::prune_cache(days)?,
+        },
     }
     Ok(())
 }
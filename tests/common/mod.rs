@@ -1,10 +1,65 @@
+#![allow(dead_code)]
+
 use std::{
     fs,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Mutex, MutexGuard},
 };
 
+use hmm_rs::hmm::{
+    cache::{sha256_file, Cache},
+    config, registry,
+};
+use tempfile::TempDir;
 use yansi::Paint;
 
+// The commands work relative to the current directory, so tests that touch
+// `.haxelib/` take this lock and run one at a time inside a temp directory
+static CWD_LOCK: Mutex<()> = Mutex::new(());
+
+pub struct TempProject {
+    pub dir: TempDir,
+    previous_dir: PathBuf,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl TempProject {
+    pub fn new() -> Self {
+        let guard = CWD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        let previous_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(dir.path()).unwrap();
+        // keeps tests away from the real per-user cache
+        std::env::set_var("HMM_CACHE_DIR", dir.path().join("cache"));
+        config::set(config::Settings::default());
+
+        Self {
+            dir,
+            previous_dir,
+            _guard: guard,
+        }
+    }
+
+    /// Copies a json file from tests/samples into the project as hmm.json
+    pub fn with_sample(sample: &str) -> Self {
+        let project = Self::new();
+        fs::copy(get_samples_dir().join(sample), project.json_path()).unwrap();
+        project
+    }
+
+    pub fn json_path(&self) -> PathBuf {
+        self.dir.path().join("hmm.json")
+    }
+}
+
+impl Drop for TempProject {
+    fn drop(&mut self) {
+        std::env::set_current_dir(&self.previous_dir).unwrap();
+    }
+}
+
+#[allow(unused_variables)]
 pub fn setup() {
     // some setup code, like creating required files/directories, starting
     // servers, etc.
@@ -14,7 +69,7 @@ pub fn setup() {
 }
 
 pub fn remove_haxelib_folder() {
-    let haxelib_path = get_samples_dir().join(".haxelib");
+    let haxelib_path = PathBuf::from(".haxelib");
     match fs::remove_dir_all(&haxelib_path) {
         Ok(_) => println!("{} .haxelib/ folder removed", "Removed".green().bold()),
         Err(e) => {
@@ -28,7 +83,7 @@ pub fn remove_haxelib_folder() {
 }
 
 pub fn setup_haxelib_folder() {
-    let haxelib_path = get_samples_dir().join(".haxelib");
+    let haxelib_path = PathBuf::from(".haxelib");
     if haxelib_path.exists() {
         fs::remove_dir_all(&haxelib_path).unwrap();
     }
@@ -47,3 +102,111 @@ pub fn get_samples_dir() -> PathBuf {
     let tests_dir = crate_dir.join("tests");
     tests_dir.join("samples")
 }
+
+/// Runs a git command in `dir`, used to build small fixture repositories
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "hmm-rs")
+        .env("GIT_AUTHOR_EMAIL", "hmm-rs@example.com")
+        .env("GIT_COMMITTER_NAME", "hmm-rs")
+        .env("GIT_COMMITTER_EMAIL", "hmm-rs@example.com")
+        .output()
+        .expect("failed to run git");
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Creates a git repository at `dir` with a single commit on `master`
+pub fn init_git_repo(dir: &Path) -> String {
+    fs::create_dir_all(dir).unwrap();
+    git(dir, &["init", "-q", "-b", "master"]);
+    fs::write(dir.join("haxelib.json"), "{\"name\": \"fixture\"}").unwrap();
+    git(dir, &["add", "-A"]);
+    git(dir, &["commit", "-q", "-m", "initial commit"]);
+    git(dir, &["rev-parse", "HEAD"])
+}
+
+/// Adds a commit to the repository at `dir` that writes `contents` to `file`
+pub fn git_commit_file(dir: &Path, file: &str, contents: &str) -> String {
+    fs::write(dir.join(file), contents).unwrap();
+    git(dir, &["add", "-A"]);
+    git(dir, &["commit", "-q", "-m", &format!("update {}", file)]);
+    git(dir, &["rev-parse", "HEAD"])
+}
+
+/// Puts a haxelib zip containing just a haxelib.json into the download cache, returning its SHA-256
+pub fn cache_haxelib_zip(name: &str, version: &str) -> String {
+    let cache = Cache::user().unwrap();
+    let download = cache.download_file(name, version).unwrap();
+    write_zip(
+        download.path(),
+        "haxelib.json",
+        &format!("{{\"name\": \"{}\"}}", name),
+    );
+    let sha256 = sha256_file(download.path()).unwrap();
+    let url = registry::open(registry::DEFAULT_REGISTRY).archive_url(name, version);
+    cache
+        .store_zip(download, name, version, &sha256, &url)
+        .unwrap();
+    sha256
+}
+
+/// Writes a zip containing a single file, like a tiny haxelib download
+pub fn write_zip(path: &Path, file: &str, contents: &str) {
+    let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+    zip.start_file(file, zip::write::SimpleFileOptions::default())
+        .unwrap();
+    std::io::Write::write_all(&mut zip, contents.as_bytes()).unwrap();
+    zip.finish().unwrap();
+}
+
+/// Serves `routes` over http on a random local port, standing in for a haxelib registry.
+/// Anything else gets a 404. Returns the base url
+pub fn serve_registry(routes: Vec<(String, Vec<u8>)>) -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let mut request_line = String::new();
+            let mut reader = std::io::BufReader::new(&stream);
+            if std::io::BufRead::read_line(&mut reader, &mut request_line).is_err() {
+                continue;
+            }
+            // GET /p/lime/8.1.0/download?query HTTP/1.1
+            let path = request_line.split(' ').nth(1).unwrap_or_default();
+            let path = path.split('?').next().unwrap_or_default();
+
+            let (status, body) = match routes.iter().find(|(route, _)| route == path) {
+                Some((_, body)) => ("200 OK", body.clone()),
+                None => ("404 Not Found", b"not found".to_vec()),
+            };
+            let header = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            );
+            let _ = std::io::Write::write_all(&mut stream, header.as_bytes());
+            let _ = std::io::Write::write_all(&mut stream, &body);
+        }
+    });
+
+    url
+}
+
+/// A url nothing is listening on, for a registry that's down
+pub fn dead_registry() -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
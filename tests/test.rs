@@ -7,8 +7,12 @@ use hmm_rs::{
 };
 mod common;
 
+use common::TempProject;
+use std::path::Path;
+
 #[test]
 fn test_clean_haxelib_folder() {
+    let _project = TempProject::new();
     common::setup_haxelib_folder();
     assert!(clean_command::remove_haxelib_folder().is_ok());
     assert!(clean_command::remove_haxelib_folder().is_err());
@@ -16,6 +20,7 @@ fn test_clean_haxelib_folder() {
 
 #[test]
 fn test_create_haxelib_folder() {
+    let _project = TempProject::new();
     remove_haxelib_folder();
     assert!(init_command::create_haxelib_folder().is_ok());
     assert!(init_command::create_haxelib_folder().is_err());
@@ -31,3 +36,1071 @@ fn test_hmm_json_read() {
         .join("flixel.json");
     assert!(hmm::json::read_json(&flixel_json).is_ok());
 }
+
+#[test]
+fn test_remove_haxelibs() {
+    let project = TempProject::with_sample("hmm.json");
+    std::fs::create_dir_all(".haxelib/flixel-addons/3,3,0").unwrap();
+    std::fs::create_dir_all(".haxelib/hxcpp/git").unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let libs = vec!["flixel-addons".to_string(), "hxcpp".to_string()];
+    remove_command::remove_haxelibs(&libs, false, deps, project.json_path()).unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    assert_eq!(deps.dependencies.len(), 1);
+    assert!(!PathBuf::from(".haxelib/flixel-addons").exists());
+    assert!(!PathBuf::from(".haxelib/hxcpp").exists());
+}
+
+#[test]
+fn test_remove_haxelibs_keep_files() {
+    let project = TempProject::with_sample("hmm.json");
+    std::fs::create_dir_all(".haxelib/hxcpp/git").unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let libs = vec!["hxcpp".to_string()];
+    remove_command::remove_haxelibs(&libs, true, deps, project.json_path()).unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    assert!(deps.get_haxelib("hxcpp").is_err());
+    assert!(PathBuf::from(".haxelib/hxcpp/git").exists());
+}
+
+#[test]
+fn test_remove_unknown_haxelib() {
+    let project = TempProject::with_sample("hmm.json");
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let libs = vec!["hxcpp".to_string(), "not-a-lib".to_string()];
+    assert!(remove_command::remove_haxelibs(&libs, false, deps, project.json_path()).is_err());
+
+    // nothing should be removed when any of the names are unknown
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    assert_eq!(deps.dependencies.len(), 3);
+}
+
+#[test]
+fn test_import_from_hxml() {
+    let project = TempProject::with_sample("hmm.json");
+    let hxml = vec![common::get_samples_dir().join("build.hxml")];
+    fromhxml_command::import_from_hxml(&hxml, project.json_path()).unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let names: Vec<&str> = deps.dependencies.iter().map(|h| h.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["flixel", "flixel-addons", "hxcpp", "lime", "openfl"]
+    );
+
+    // a bare `-lib` keeps the version hmm.json already had
+    let addons = deps.get_haxelib("flixel-addons").unwrap();
+    assert_eq!(addons.version.as_deref(), Some("3.3.0"));
+    // a pinned one replaces it
+    let hxcpp = deps.get_haxelib("hxcpp").unwrap();
+    assert_eq!(hxcpp.vcs_ref.as_deref(), Some("v4.3.70"));
+    let lime = deps.get_haxelib("lime").unwrap();
+    assert_eq!(lime.haxelib_type, hmm::haxelib::HaxelibType::Haxelib);
+    assert_eq!(lime.version.as_deref(), Some("8.1.0"));
+    assert_eq!(deps.get_haxelib("openfl").unwrap().version, None);
+    // untouched by the hxml
+    let flixel = deps.get_haxelib("flixel").unwrap();
+    assert_eq!(flixel.vcs_ref.as_deref(), Some("master"));
+
+    // without an hmm.json, one gets made from just the hxml
+    std::fs::remove_file(project.json_path()).unwrap();
+    fromhxml_command::import_from_hxml(&hxml, project.json_path()).unwrap();
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    assert_eq!(deps.dependencies.len(), 4);
+    assert_eq!(deps.get_haxelib("flixel-addons").unwrap().version, None);
+}
+
+#[test]
+fn test_lock_haxelib_version() {
+    let project = TempProject::with_sample("version_null.json");
+    std::fs::create_dir_all(".haxelib/format").unwrap();
+    std::fs::write(".haxelib/format/.current", "3.5.0").unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    lock_command::lock_dependencies(&None, deps, project.json_path()).unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let format = deps.get_haxelib("format").unwrap();
+    assert_eq!(format.version.as_deref(), Some("3.5.0"));
+}
+
+#[test]
+fn test_lock_git_branch() {
+    let project = TempProject::with_sample("flixel.json");
+    let commit = common::init_git_repo(&project.dir.path().join(".haxelib/flixel/git"));
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let libs = Some(vec!["flixel".to_string()]);
+    lock_command::lock_dependencies(&libs, deps, project.json_path()).unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let flixel = deps.get_haxelib("flixel").unwrap();
+    assert_eq!(flixel.vcs_ref.as_deref(), Some(commit.as_str()));
+}
+
+#[test]
+fn test_lock_requested_library_fails() {
+    let project = TempProject::with_sample("version_null.json");
+
+    // format isn't installed, so there's no version to lock it to
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let libs = Some(vec!["format".to_string()]);
+    assert!(lock_command::lock_dependencies(&libs, deps, project.json_path()).is_err());
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    assert!(lock_command::lock_dependencies(&None, deps, project.json_path()).is_ok());
+}
+
+#[test]
+fn test_install_dev_dependency() {
+    let project = TempProject::with_sample("dev.json");
+    let dev_dir = project.dir.path().join("flixel-local");
+    std::fs::create_dir_all(&dev_dir).unwrap();
+    std::fs::write(
+        dev_dir.join("haxelib.json"),
+        r#"{"name": "flixel", "version": "6.0.0", "classPath": "src"}"#,
+    )
+    .unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    install_command::install_from_hmm(&deps, &project.json_path(), false).unwrap();
+
+    let dev_file = std::fs::read_to_string(".haxelib/flixel/.dev").unwrap();
+    assert_eq!(PathBuf::from(dev_file), dev_dir);
+
+    let statuses = check_command::compare_haxelib_to_hmm(&deps, &project.json_path()).unwrap();
+    assert_eq!(
+        statuses[0].install_type,
+        check_command::InstallType::AlreadyInstalled
+    );
+    assert!(check_command::check(&deps, &project.json_path(), OutputFormat::Json).is_ok());
+
+    let hxml_path = project.dir.path().join("build.hxml");
+    tohxml_command::dump_to_hxml(&deps, Some(hxml_path.clone())).unwrap();
+    let hxml = std::fs::read_to_string(hxml_path).unwrap();
+    assert_eq!(hxml, "-lib flixel\n");
+}
+
+#[test]
+fn test_check_dev_dependency_missing_haxelib_json() {
+    let project = TempProject::with_sample("dev.json");
+    std::fs::create_dir_all(".haxelib/flixel").unwrap();
+    std::fs::write(
+        ".haxelib/flixel/.dev",
+        project
+            .dir
+            .path()
+            .join("flixel-local")
+            .display()
+            .to_string(),
+    )
+    .unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let statuses = check_command::compare_haxelib_to_hmm(&deps, &project.json_path()).unwrap();
+    assert_eq!(
+        statuses[0].install_type,
+        check_command::InstallType::Missing
+    );
+    assert!(check_command::check(&deps, &project.json_path(), OutputFormat::Json).is_err());
+}
+
+#[test]
+fn test_dev_command_replaces_entry() {
+    let project = TempProject::with_sample("hmm.json");
+    let dev_dir = project.dir.path().join("flixel-local");
+    std::fs::create_dir_all(&dev_dir).unwrap();
+    std::fs::write(dev_dir.join("haxelib.json"), r#"{"name": "flixel"}"#).unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    dev_command::install_dev(
+        "flixel",
+        &PathBuf::from("flixel-local"),
+        deps,
+        project.json_path(),
+    )
+    .unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    assert_eq!(deps.dependencies.len(), 3);
+    let flixel = deps.get_haxelib("flixel").unwrap();
+    assert_eq!(flixel.haxelib_type, hmm::haxelib::HaxelibType::Dev);
+    assert_eq!(flixel.dir.as_deref(), Some("flixel-local"));
+    assert!(PathBuf::from(".haxelib/flixel/.dev").exists());
+}
+
+#[test]
+fn test_upsert_haxelib() {
+    let flixel_json = common::get_samples_dir().join("hmm.json");
+    let mut deps = hmm::json::read_json(&flixel_json).unwrap();
+
+    let mut addons = deps.get_haxelib("flixel-addons").unwrap().clone();
+    addons.version = Some("3.3.2".to_string());
+    let previous = deps.upsert_haxelib(addons).unwrap();
+    assert_eq!(previous.version.as_deref(), Some("3.3.0"));
+    assert_eq!(deps.dependencies.len(), 3);
+
+    let mut lime = previous.clone();
+    lime.name = "lime".to_string();
+    assert!(deps.upsert_haxelib(lime).is_none());
+    assert_eq!(deps.dependencies.len(), 4);
+}
+
+#[test]
+fn test_reinstall_dev_dependency() {
+    let project = TempProject::with_sample("dev.json");
+    let dev_dir = project.dir.path().join("flixel-local");
+    std::fs::create_dir_all(&dev_dir).unwrap();
+    std::fs::write(dev_dir.join("haxelib.json"), r#"{"name": "flixel"}"#).unwrap();
+    std::fs::create_dir_all(".haxelib/flixel").unwrap();
+    std::fs::write(".haxelib/flixel/.dev", "somewhere/else").unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let unknown = Some(vec!["lime".to_string()]);
+    assert!(reinstall_command::reinstall_from_hmm(&deps, &unknown, &project.json_path()).is_err());
+
+    reinstall_command::reinstall_from_hmm(&deps, &None, &project.json_path()).unwrap();
+    let dev_file = std::fs::read_to_string(".haxelib/flixel/.dev").unwrap();
+    assert_eq!(PathBuf::from(dev_file), dev_dir);
+}
+
+#[test]
+fn test_reinstall_offline() {
+    let project = TempProject::new();
+    let deps = hmm::dependencies::Dependancies {
+        dependencies: vec![hmm::hxml::parse_lib("lime").unwrap()],
+        registries: Vec::new(),
+    };
+    std::fs::create_dir_all(".haxelib/lime/8,1,0").unwrap();
+    std::fs::write(".haxelib/lime/8,1,0/haxelib.json", r#"{"name": "lime"}"#).unwrap();
+    std::fs::write(".haxelib/lime/.current", "8.1.0").unwrap();
+    hmm::config::set(hmm::config::Settings {
+        offline: true,
+        ..Default::default()
+    });
+
+    // nothing gets removed when it can't be put back
+    let err = reinstall_command::reinstall_from_hmm(&deps, &None, &project.json_path())
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("lime 8.1.0"));
+    assert!(Path::new(".haxelib/lime/8,1,0/haxelib.json").exists());
+
+    common::cache_haxelib_zip("lime", "8.1.0");
+    reinstall_command::reinstall_from_hmm(&deps, &None, &project.json_path()).unwrap();
+    let lockfile =
+        hmm::lockfile::Lockfile::read(&project.dir.path().join("hmm-lock.json")).unwrap();
+    assert_eq!(lockfile.dependencies[0].version.as_deref(), Some("8.1.0"));
+}
+
+#[test]
+fn test_update_git_branch() {
+    let project = TempProject::with_sample("flixel.json");
+    let origin = project.dir.path().join("origin");
+    common::init_git_repo(&origin);
+    std::fs::create_dir_all(".haxelib/flixel").unwrap();
+    common::git(
+        project.dir.path(),
+        &["clone", "-q", "origin", ".haxelib/flixel/git"],
+    );
+    let newest = common::git_commit_file(&origin, "README.md", "new commit");
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    update_command::update_dependencies(&None, true, deps, project.json_path()).unwrap();
+    let clone = project.dir.path().join(".haxelib/flixel/git");
+    assert_ne!(common::git(&clone, &["rev-parse", "HEAD"]), newest);
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    update_command::update_dependencies(&None, false, deps, project.json_path()).unwrap();
+    assert_eq!(common::git(&clone, &["rev-parse", "HEAD"]), newest);
+}
+
+#[test]
+fn test_update_fails_without_writing_lockfile() {
+    let project = TempProject::with_sample("flixel.json");
+
+    // flixel was never cloned, so it can't be updated
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    assert!(update_command::update_dependencies(&None, false, deps, project.json_path()).is_err());
+    assert!(!project.dir.path().join("hmm-lock.json").exists());
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    assert!(update_command::update_dependencies(&None, true, deps, project.json_path()).is_err());
+}
+
+#[test]
+fn test_update_haxelib_keeps_version_when_install_fails() {
+    let project = TempProject::new();
+    std::fs::write(
+        project.json_path(),
+        r#"{"dependencies": [
+            {"name": "lime", "type": "haxelib", "version": "8.0.0"},
+            {"name": "format", "type": "haxelib", "version": "3.0.0"}
+        ]}"#,
+    )
+    .unwrap();
+
+    // every library's latest version is 8.1.0, but only lime's zip can be downloaded
+    let zip_path = project.dir.path().join("lime.zip");
+    common::write_zip(&zip_path, "haxelib.json", "{\"name\": \"lime\"}");
+    let registry = common::serve_registry(vec![
+        (
+            "/p/lime/8.1.0/download".to_string(),
+            std::fs::read(&zip_path).unwrap(),
+        ),
+        ("/api/3.0/index.n/".to_string(), b"hxry5:8.1.0".to_vec()),
+    ]);
+    hmm::config::set(hmm::config::Settings {
+        registries: vec![registry],
+        ..Default::default()
+    });
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    assert!(update_command::update_dependencies(&None, false, deps, project.json_path()).is_err());
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let version = |name: &str| deps.get_haxelib(name).unwrap().version.clone().unwrap();
+    assert_eq!(version("lime"), "8.1.0");
+    assert_eq!(version("format"), "3.0.0");
+    assert!(!project.dir.path().join("hmm-lock.json").exists());
+}
+
+#[test]
+fn test_outdated_git_branch() {
+    let project = TempProject::with_sample("flixel.json");
+    let origin = project.dir.path().join("origin");
+    common::init_git_repo(&origin);
+    std::fs::create_dir_all(".haxelib/flixel").unwrap();
+    common::git(
+        project.dir.path(),
+        &["clone", "-q", "origin", ".haxelib/flixel/git"],
+    );
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let json = OutputFormat::Json;
+    assert!(outdated_command::report_outdated(&deps, &project.json_path(), json).is_ok());
+
+    common::git_commit_file(&origin, "README.md", "new commit");
+    assert!(outdated_command::report_outdated(&deps, &project.json_path(), json).is_err());
+}
+
+#[test]
+fn test_outdated_unreachable_remote() {
+    let project = TempProject::with_sample("flixel.json");
+    let origin = project.dir.path().join("origin");
+    common::init_git_repo(&origin);
+    std::fs::create_dir_all(".haxelib/flixel").unwrap();
+    common::git(
+        project.dir.path(),
+        &["clone", "-q", "origin", ".haxelib/flixel/git"],
+    );
+    std::fs::remove_dir_all(&origin).unwrap();
+
+    // up to date as far as the clone knows, but the remote can't be asked
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let error = outdated_command::report_outdated(&deps, &project.json_path(), OutputFormat::Json)
+        .unwrap_err();
+    assert!(error.to_string().contains("couldn't be checked"));
+}
+
+#[test]
+fn test_resolve_transitive_dependencies() {
+    let project = TempProject::with_sample("dev.json");
+    let dev_dir = project.dir.path().join("flixel-local");
+    std::fs::create_dir_all(&dev_dir).unwrap();
+    std::fs::write(
+        dev_dir.join("haxelib.json"),
+        r#"{"name": "flixel", "dependencies": {"lime": "", "openfl": "9.3.3"}}"#,
+    )
+    .unwrap();
+
+    std::fs::create_dir_all(".haxelib/lime/8,1,0").unwrap();
+    std::fs::write(".haxelib/lime/.current", "8.1.0").unwrap();
+    std::fs::write(
+        ".haxelib/lime/8,1,0/haxelib.json",
+        r#"{"name": "lime", "dependencies": {"format": "3.5.0"}}"#,
+    )
+    .unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let graph = hmm::resolver::resolve(&deps, &project.json_path()).unwrap();
+
+    let transitive: Vec<(&str, bool)> = graph
+        .transitive
+        .iter()
+        .map(|t| (t.haxelib.name.as_str(), t.installed))
+        .collect();
+    assert_eq!(
+        transitive,
+        vec![("lime", true), ("openfl", false), ("format", false)]
+    );
+
+    let missing: Vec<&str> = graph.missing().map(|t| t.haxelib.name.as_str()).collect();
+    assert_eq!(missing, vec!["openfl", "format"]);
+    assert_eq!(
+        graph.transitive[1].haxelib.version.as_deref(),
+        Some("9.3.3")
+    );
+    assert_eq!(graph.transitive[2].required_by[0].required_by, "lime");
+}
+
+#[test]
+fn test_resolve_installed_version() {
+    let project = TempProject::with_sample("dev.json");
+    let dev_dir = project.dir.path().join("flixel-local");
+    std::fs::create_dir_all(&dev_dir).unwrap();
+    std::fs::write(
+        dev_dir.join("haxelib.json"),
+        r#"{"name": "flixel", "dependencies": {"openfl": "9.3.3"}}"#,
+    )
+    .unwrap();
+
+    // installed, but not at the version flixel asks for
+    std::fs::create_dir_all(".haxelib/openfl/9,2,0").unwrap();
+    std::fs::write(".haxelib/openfl/.current", "9.2.0").unwrap();
+    std::fs::write(".haxelib/openfl/9,2,0/haxelib.json", "{").unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let err = hmm::resolver::resolve(&deps, &project.json_path())
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("openfl's haxelib.json"));
+
+    std::fs::write(
+        ".haxelib/openfl/9,2,0/haxelib.json",
+        r#"{"name": "openfl"}"#,
+    )
+    .unwrap();
+    let graph = hmm::resolver::resolve(&deps, &project.json_path()).unwrap();
+    let missing: Vec<&str> = graph.missing().map(|t| t.haxelib.name.as_str()).collect();
+    assert_eq!(missing, vec!["openfl"]);
+}
+
+#[test]
+fn test_version_conflicts() {
+    let project = TempProject::new();
+    let dev_dir = project.dir.path().join("flixel-local");
+    std::fs::create_dir_all(&dev_dir).unwrap();
+    std::fs::write(
+        dev_dir.join("haxelib.json"),
+        r#"{"name": "flixel", "dependencies": {"lime": "8.1.0"}}"#,
+    )
+    .unwrap();
+
+    std::fs::create_dir_all(".haxelib/lime/8,0,0").unwrap();
+    std::fs::write(".haxelib/lime/.current", "8.0.0").unwrap();
+    std::fs::write(".haxelib/lime/8,0,0/haxelib.json", r#"{"name": "lime"}"#).unwrap();
+
+    let hmm_json = r#"{"dependencies": [
+        {"name": "flixel", "type": "dev", "dir": "flixel-local"},
+        {"name": "lime", "type": "haxelib", "version": "8.0.0"},
+        {"name": "format", "type": "haxelib", "version": "3.5.0"}
+    ]}"#;
+    std::fs::write(project.json_path(), hmm_json).unwrap();
+
+    let mut deps = hmm::json::read_json(&project.json_path()).unwrap();
+    install_command::install_from_dev(&deps.dependencies[0], &project.json_path()).unwrap();
+
+    let graph = hmm::resolver::resolve(&deps, &project.json_path()).unwrap();
+    assert_eq!(graph.conflicts.len(), 1);
+    let conflict = &graph.conflicts[0];
+    assert_eq!(conflict.name, "lime");
+    assert_eq!(conflict.pinned.as_deref(), Some("8.0.0"));
+    assert_eq!(conflict.requirements[0].required_by, "flixel");
+    assert_eq!(conflict.requirements[0].version, "8.1.0");
+    assert!(!conflict.overridden);
+
+    assert!(check_command::check(&deps, &project.json_path(), OutputFormat::Text).is_err());
+    assert!(check_command::check(&deps, &project.json_path(), OutputFormat::Json).is_err());
+    common::cache_haxelib_zip("format", "3.5.0");
+    assert!(install_command::install_from_hmm(&deps, &project.json_path(), false).is_err());
+    // refused before anything got installed
+    assert!(!Path::new(".haxelib/format").exists());
+
+    deps.dependencies[1].overrides = true;
+    let graph = hmm::resolver::resolve(&deps, &project.json_path()).unwrap();
+    assert_eq!(graph.unresolved_conflicts().count(), 0);
+    assert!(install_command::install_from_hmm(&deps, &project.json_path(), false).is_ok());
+    assert!(check_command::check(&deps, &project.json_path(), OutputFormat::Text).is_ok());
+}
+
+#[test]
+fn test_lockfile() {
+    let project = TempProject::with_sample("dev.json");
+    let dev_dir = project.dir.path().join("flixel-local");
+    std::fs::create_dir_all(&dev_dir).unwrap();
+    std::fs::write(
+        dev_dir.join("haxelib.json"),
+        r#"{"name": "flixel", "dependencies": {"lime": "8.1.0"}}"#,
+    )
+    .unwrap();
+
+    std::fs::create_dir_all(".haxelib/lime/8,1,0").unwrap();
+    std::fs::write(".haxelib/lime/.current", "8.1.0").unwrap();
+    let record = hmm::lockfile::DownloadRecord {
+        url: "https://lib.haxe.org/p/lime/8.1.0/download".to_string(),
+        sha256: "0123abcd".to_string(),
+    };
+    record.write(Path::new(".haxelib/lime/8,1,0")).unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    install_command::install_from_hmm(&deps, &project.json_path(), false).unwrap();
+
+    let lockfile_path = hmm::lockfile::lockfile_path(&project.json_path());
+    let lockfile = hmm::lockfile::Lockfile::read(&lockfile_path).unwrap();
+    assert_eq!(lockfile.dependencies.len(), 2);
+    assert_eq!(
+        lockfile.dependencies[0].dir.as_deref(),
+        Some("flixel-local")
+    );
+    let lime = &lockfile.dependencies[1];
+    assert_eq!(lime.version.as_deref(), Some("8.1.0"));
+    assert_eq!(lime.sha256.as_deref(), Some("0123abcd"));
+
+    std::fs::remove_dir_all(".haxelib/flixel").unwrap();
+    install_command::install_from_lockfile(&project.json_path()).unwrap();
+    assert!(Path::new(".haxelib/flixel/.dev").exists());
+}
+
+#[test]
+fn test_install_frozen() {
+    let project = TempProject::with_sample("dev.json");
+    let dev_dir = project.dir.path().join("flixel-local");
+    std::fs::create_dir_all(&dev_dir).unwrap();
+    std::fs::write(dev_dir.join("haxelib.json"), r#"{"name": "flixel"}"#).unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    // there's no hmm-lock.json to compare against yet
+    assert!(install_command::install_from_hmm(&deps, &project.json_path(), true).is_err());
+    install_command::install_from_hmm(&deps, &project.json_path(), false).unwrap();
+    install_command::install_from_hmm(&deps, &project.json_path(), true).unwrap();
+
+    std::fs::create_dir_all(".haxelib/lime/8,1,0").unwrap();
+    std::fs::write(".haxelib/lime/.current", "8.1.0").unwrap();
+    std::fs::write(
+        dev_dir.join("haxelib.json"),
+        r#"{"name": "flixel", "dependencies": {"lime": "8.1.0"}}"#,
+    )
+    .unwrap();
+    assert!(install_command::install_from_hmm(&deps, &project.json_path(), true).is_err());
+
+    let mut deps = hmm::json::read_json(&project.json_path()).unwrap();
+    deps.dependencies
+        .push(hmm::hxml::parse_lib("lime").unwrap());
+    let err = install_command::install_from_hmm(&deps, &project.json_path(), true).unwrap_err();
+    assert!(err.to_string().contains("lime has `\"version\": null`"));
+}
+
+#[test]
+fn test_install_frozen_git_branch() {
+    let project = TempProject::with_sample("flixel.json");
+    common::init_git_repo(&project.dir.path().join(".haxelib/flixel/git"));
+    std::fs::write(".haxelib/flixel/.current", "git").unwrap();
+
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    let err = install_command::install_from_hmm(&deps, &project.json_path(), true).unwrap_err();
+    assert!(err.to_string().contains("branch master"));
+}
+
+#[test]
+fn test_install_frozen_git_branch_before_cloning() {
+    let project = TempProject::new();
+    let origin = project.dir.path().join("origin");
+    common::init_git_repo(&origin);
+    common::git(&origin, &["tag", "v1"]);
+
+    let write_json = |vcs_ref: &str| {
+        let json = format!(
+            r#"{{"dependencies": [{{"name": "fixture", "type": "git", "ref": "{}", "url": "{}"}}]}}"#,
+            vcs_ref,
+            origin.display()
+        );
+        std::fs::write(project.json_path(), json).unwrap();
+        hmm::json::read_json(&project.json_path()).unwrap()
+    };
+
+    // the remote's refs get listed, without cloning anything
+    let deps = write_json("master");
+    let err = install_command::install_from_hmm(&deps, &project.json_path(), true).unwrap_err();
+    assert!(err.to_string().contains("branch master"));
+    assert!(!Path::new(".haxelib/fixture").exists());
+
+    // a tag is pinned, so it gets as far as the missing hmm-lock.json
+    let deps = write_json("v1");
+    let err = install_command::install_from_hmm(&deps, &project.json_path(), true).unwrap_err();
+    assert!(!err.to_string().contains("branch"));
+    assert!(!Path::new(".haxelib/fixture").exists());
+}
+
+#[test]
+fn test_install_frozen_refuses_before_installing() {
+    let project = TempProject::new();
+    common::cache_haxelib_zip("lime", "8.1.0");
+    common::cache_haxelib_zip("lime", "8.2.0");
+    let deps_at = |version: &str| hmm::dependencies::Dependancies {
+        dependencies: vec![hmm::hxml::parse_lib(&format!("lime:{}", version)).unwrap()],
+        registries: Vec::new(),
+    };
+
+    let err = install_command::install_from_hmm(&deps_at("8.1.0"), &project.json_path(), true)
+        .unwrap_err();
+    assert!(err.to_string().contains("`--frozen`"));
+    assert!(!Path::new(".haxelib/lime").exists());
+
+    install_command::install_from_hmm(&deps_at("8.1.0"), &project.json_path(), false).unwrap();
+    let err = install_command::install_from_hmm(&deps_at("8.2.0"), &project.json_path(), true)
+        .unwrap_err();
+    assert!(err.to_string().contains("locked at 8.1.0"));
+    assert!(!Path::new(".haxelib/lime/8,2,0").exists());
+    assert_eq!(
+        std::fs::read_to_string(".haxelib/lime/.current").unwrap(),
+        "8.1.0"
+    );
+}
+
+#[test]
+fn test_install_haxelib_without_version() {
+    let _project = TempProject::new();
+    let lime = hmm::hxml::parse_lib("lime").unwrap();
+    let err = install_command::install_from_haxelib(&lime).unwrap_err();
+    assert!(err.to_string().contains("lime has `\"version\": null`"));
+}
+
+#[test]
+fn test_install_haxelib_from_cache() {
+    let _project = TempProject::new();
+    let cache = hmm::cache::Cache::user().unwrap();
+    let sha256 = common::cache_haxelib_zip("lime", "8.1.0");
+
+    // the cache is checked first, so this never touches the network
+    let lime = hmm::hxml::parse_lib("lime:8.1.0").unwrap();
+    install_command::install_from_haxelib(&lime).unwrap();
+
+    assert!(Path::new(".haxelib/lime/8,1,0/haxelib.json").exists());
+    let record = hmm::lockfile::DownloadRecord::read(Path::new(".haxelib/lime/8,1,0")).unwrap();
+    assert_eq!(record.sha256, sha256);
+    assert_eq!(cache.list_zips().unwrap().len(), 1);
+}
+
+#[test]
+fn test_install_offline() {
+    let project = TempProject::with_sample("flixel.json");
+    let mut deps = hmm::json::read_json(&project.json_path()).unwrap();
+    deps.dependencies
+        .push(hmm::hxml::parse_lib("lime:8.1.0").unwrap());
+    hmm::config::set(hmm::config::Settings {
+        offline: true,
+        ..Default::default()
+    });
+
+    let err = install_command::install_from_hmm(&deps, &project.json_path(), false)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("lime 8.1.0"));
+    assert!(err.contains("flixel https://github.com/haxeflixel/flixel#master"));
+
+    common::cache_haxelib_zip("lime", "8.1.0");
+    let origin = project.dir.path().join("origin");
+    common::init_git_repo(&origin);
+    let cache = hmm::cache::Cache::user().unwrap();
+    let cached_repo = cache.repo_path("https://github.com/haxeflixel/flixel");
+    common::git(
+        project.dir.path(),
+        &[
+            "clone",
+            "-q",
+            "--bare",
+            "origin",
+            cached_repo.to_str().unwrap(),
+        ],
+    );
+
+    install_command::install_from_hmm(&deps, &project.json_path(), false).unwrap();
+    assert!(Path::new(".haxelib/lime/8,1,0/haxelib.json").exists());
+    assert!(Path::new(".haxelib/flixel/git/haxelib.json").exists());
+}
+
+#[test]
+fn test_registry_fallback() {
+    let project = TempProject::new();
+    let zip_path = project.dir.path().join("lime.zip");
+    common::write_zip(&zip_path, "haxelib.json", "{\"name\": \"lime\"}");
+
+    let mirror = common::serve_registry(vec![
+        (
+            "/p/lime/8.1.0/download".to_string(),
+            std::fs::read(&zip_path).unwrap(),
+        ),
+        // a haxe remoting reply holding the string "8.1.0"
+        ("/api/3.0/index.n/".to_string(), b"hxry5:8.1.0".to_vec()),
+    ]);
+    let missing = common::serve_registry(vec![]);
+    hmm::config::set(hmm::config::Settings {
+        registries: vec![common::dead_registry(), missing, mirror.clone()],
+        ..Default::default()
+    });
+
+    assert_eq!(
+        haxelib_command::get_latest_version("lime").unwrap(),
+        "8.1.0"
+    );
+
+    let lime = hmm::hxml::parse_lib("lime:8.1.0").unwrap();
+    install_command::install_from_haxelib(&lime).unwrap();
+
+    let record = hmm::lockfile::DownloadRecord::read(Path::new(".haxelib/lime/8,1,0")).unwrap();
+    assert_eq!(record.url, format!("{}/p/lime/8.1.0/download", mirror));
+
+    // installing again comes from the cache, which remembers the mirror
+    remove_haxelib_folder();
+    install_command::install_from_haxelib(&lime).unwrap();
+    let record = hmm::lockfile::DownloadRecord::read(Path::new(".haxelib/lime/8,1,0")).unwrap();
+    assert_eq!(record.url, format!("{}/p/lime/8.1.0/download", mirror));
+}
+
+#[test]
+fn test_install_from_folder_registry() {
+    let project = TempProject::new();
+    let registry_dir = project.dir.path().join("registry");
+    std::fs::create_dir_all(registry_dir.join("lime")).unwrap();
+    for version in ["8.0.2", "8.1.0"] {
+        common::write_zip(
+            &registry_dir.join("lime").join(format!("{}.zip", version)),
+            "haxelib.json",
+            "{\"name\": \"lime\"}",
+        );
+    }
+    // a folder registry needs no network, so this works fully offline
+    hmm::config::set(hmm::config::Settings {
+        offline: true,
+        registries: vec![registry_dir.display().to_string()],
+        ..Default::default()
+    });
+
+    assert_eq!(
+        haxelib_command::get_latest_version("lime").unwrap(),
+        "8.1.0"
+    );
+
+    let deps = hmm::dependencies::Dependancies {
+        dependencies: vec![hmm::hxml::parse_lib("lime:8.0.2").unwrap()],
+        registries: vec![],
+    };
+    install_command::install_from_hmm(&deps, &project.json_path(), false).unwrap();
+
+    let lockfile =
+        hmm::lockfile::Lockfile::read(&hmm::lockfile::lockfile_path(&project.json_path())).unwrap();
+    let url = lockfile.dependencies[0].url.clone().unwrap();
+    assert!(url.starts_with("file://"));
+    assert!(url.ends_with("lime/8.0.2.zip"));
+}
+
+#[test]
+fn test_git_checkout() {
+    let project = TempProject::new();
+    let origin = project.dir.path().join("origin");
+    common::init_git_repo(&origin);
+    let v1 = common::git_commit_file(&origin, "Old.hx", "class Old {}");
+    common::git(&origin, &["tag", "-a", "v1", "-m", "version 1"]);
+    std::fs::remove_file(origin.join("Old.hx")).unwrap();
+    std::fs::create_dir(origin.join("src")).unwrap();
+    let v2 = common::git_commit_file(&origin, "src/New.hx", "class New {}");
+    common::git(&origin, &["tag", "v2"]);
+    common::git(&origin, &["checkout", "-q", "-b", "dev"]);
+    let dev = common::git_commit_file(&origin, "src/Dev.hx", "class Dev {}");
+    common::git(&origin, &["checkout", "-q", "master"]);
+
+    let clone = project.dir.path().join(".haxelib/fixture/git");
+    common::git(
+        project.dir.path(),
+        &["clone", "-q", "origin", clone.to_str().unwrap()],
+    );
+    std::fs::write(".haxelib/fixture/.current", "git").unwrap();
+
+    let checkout = |vcs_ref: &str| {
+        let fixture = git_fixture(&origin, vcs_ref);
+        install_command::install_from_git_using_gix_checkout(&fixture).unwrap();
+        // anything left over would show up as a local change
+        assert_eq!(common::git(&clone, &["status", "--porcelain"]), "");
+        (
+            common::git(&clone, &["rev-parse", "HEAD"]),
+            common::git(&clone, &["rev-parse", "--abbrev-ref", "HEAD"]),
+        )
+    };
+
+    // annotated tags get peeled to their commit
+    assert_eq!(checkout("v1"), (v1.clone(), "HEAD".to_string()));
+    assert!(clone.join("Old.hx").exists());
+    assert!(!clone.join("src").exists());
+
+    // a branch that's only on the remote
+    assert_eq!(checkout("dev"), (dev, "dev".to_string()));
+    assert!(clone.join("src/Dev.hx").exists());
+    assert!(!clone.join("Old.hx").exists());
+
+    assert_eq!(checkout(&v2[..8]), (v2.clone(), "HEAD".to_string()));
+    assert!(!clone.join("src/Dev.hx").exists());
+    assert!(clone.join("src/New.hx").exists());
+
+    assert_eq!(checkout("v2"), (v2, "HEAD".to_string()));
+}
+
+#[test]
+fn test_check_annotated_tag() {
+    let project = TempProject::new();
+    let origin = project.dir.path().join("origin");
+    common::init_git_repo(&origin);
+    let tagged = common::git_commit_file(&origin, "Tagged.hx", "class Tagged {}");
+    common::git(&origin, &["tag", "-a", "v1", "-m", "version 1"]);
+    common::git_commit_file(&origin, "Later.hx", "class Later {}");
+
+    let clone = project.dir.path().join(".haxelib/fixture/git");
+    common::git(
+        project.dir.path(),
+        &["clone", "-q", "origin", clone.to_str().unwrap()],
+    );
+    std::fs::write(".haxelib/fixture/.current", "git").unwrap();
+
+    let fixture = git_fixture(&origin, "v1");
+    let install_type = || {
+        check_command::check_dependency(&fixture, &project.json_path())
+            .unwrap()
+            .install_type
+    };
+    assert_eq!(install_type(), check_command::InstallType::Outdated);
+
+    // the tag points at a tag object, HEAD at the commit it tags
+    install_command::install_from_git_using_gix_checkout(&fixture).unwrap();
+    assert_eq!(common::git(&clone, &["rev-parse", "HEAD"]), tagged);
+    assert_eq!(install_type(), check_command::InstallType::AlreadyInstalled);
+}
+
+/// A git dependency on the fixture repository at `origin`
+fn git_fixture(origin: &Path, vcs_ref: &str) -> hmm::haxelib::Haxelib {
+    hmm::haxelib::Haxelib {
+        name: "fixture".to_string(),
+        haxelib_type: hmm::haxelib::HaxelibType::Git,
+        vcs_ref: Some(vcs_ref.to_string()),
+        dir: None,
+        url: Some(origin.display().to_string()),
+        version: None,
+        overrides: false,
+        shallow: None,
+    }
+}
+
+#[test]
+fn test_git_checkout_fetches_missing_ref() {
+    let project = TempProject::new();
+    let origin = project.dir.path().join("origin");
+    common::init_git_repo(&origin);
+    let clone = project.dir.path().join(".haxelib/fixture/git");
+    common::git(
+        project.dir.path(),
+        &["clone", "-q", "origin", clone.to_str().unwrap()],
+    );
+    std::fs::write(".haxelib/fixture/.current", "git").unwrap();
+
+    // pushed after the clone was made
+    let tagged = common::git_commit_file(&origin, "Tagged.hx", "class Tagged {}");
+    common::git(&origin, &["tag", "-a", "v2", "-m", "version 2"]);
+    common::git(&origin, &["checkout", "-q", "-b", "later"]);
+    let later = common::git_commit_file(&origin, "Later.hx", "class Later {}");
+
+    install_command::install_from_git_using_gix_checkout(&git_fixture(&origin, "v2")).unwrap();
+    assert_eq!(common::git(&clone, &["rev-parse", "HEAD"]), tagged);
+
+    install_command::install_from_git_using_gix_checkout(&git_fixture(&origin, &later)).unwrap();
+    assert_eq!(common::git(&clone, &["rev-parse", "HEAD"]), later);
+    assert!(clone.join("Later.hx").exists());
+
+    let missing = git_fixture(&origin, "does-not-exist");
+    assert!(install_command::install_from_git_using_gix_checkout(&missing).is_err());
+}
+
+#[test]
+fn test_shallow_clone_deepens() {
+    let project = TempProject::new();
+    let origin = project.dir.path().join("origin");
+    let first = common::init_git_repo(&origin);
+    common::git_commit_file(&origin, "Second.hx", "class Second {}");
+    common::git_commit_file(&origin, "Third.hx", "class Third {}");
+
+    let clone = project.dir.path().join(".haxelib/fixture/git");
+    common::git(
+        project.dir.path(),
+        &[
+            "clone",
+            "-q",
+            "--depth",
+            "1",
+            "--no-tags",
+            &format!("file://{}", origin.display()),
+            clone.to_str().unwrap(),
+        ],
+    );
+    std::fs::write(".haxelib/fixture/.current", "git").unwrap();
+    assert!(clone.join(".git/shallow").exists());
+
+    // the pinned commit is older than the shallow clone goes back
+    install_command::install_from_git_using_gix_checkout(&git_fixture(&origin, &first)).unwrap();
+    assert_eq!(common::git(&clone, &["rev-parse", "HEAD"]), first);
+    assert!(!clone.join("Third.hx").exists());
+}
+
+#[test]
+fn test_git_submodules() {
+    let project = TempProject::new();
+    let native = project.dir.path().join("native");
+    let pinned = common::init_git_repo(&native);
+    common::git_commit_file(&native, "Native.hx", "class Native {}");
+
+    // relative to the parent's remote, and pinned behind the submodule's own HEAD
+    let origin = project.dir.path().join("origin");
+    common::init_git_repo(&origin);
+    common::git(
+        &origin,
+        &[
+            "-c",
+            "protocol.file.allow=always",
+            "submodule",
+            "add",
+            "-q",
+            "../native",
+            "native",
+        ],
+    );
+    common::git(&origin.join("native"), &["checkout", "-q", &pinned]);
+    common::git(&origin, &["add", "-A"]);
+    common::git(&origin, &["commit", "-q", "-m", "add native"]);
+
+    let clone = project.dir.path().join(".haxelib/fixture/git");
+    common::git(
+        project.dir.path(),
+        &["clone", "-q", "origin", clone.to_str().unwrap()],
+    );
+    std::fs::write(".haxelib/fixture/.current", "git").unwrap();
+
+    let fixture = git_fixture(&origin, "master");
+    let install_type = || {
+        check_command::check_dependency(&fixture, &project.json_path())
+            .unwrap()
+            .install_type
+    };
+    assert_eq!(
+        install_type(),
+        check_command::InstallType::OutdatedSubmodule
+    );
+
+    install_command::install_from_git_using_gix_checkout(&fixture).unwrap();
+    let submodule = clone.join("native");
+    assert_eq!(common::git(&submodule, &["rev-parse", "HEAD"]), pinned);
+    assert!(submodule.join("haxelib.json").exists());
+    assert!(!submodule.join("Native.hx").exists());
+    assert_eq!(install_type(), check_command::InstallType::AlreadyInstalled);
+
+    common::git(&submodule, &["checkout", "-q", "master"]);
+    assert_eq!(
+        install_type(),
+        check_command::InstallType::OutdatedSubmodule
+    );
+}
+
+#[test]
+fn test_install_from_local_bare_repo() {
+    let project = TempProject::new();
+    let origin = project.dir.path().join("origin");
+    common::init_git_repo(&origin);
+    let tagged = common::git_commit_file(&origin, "Tagged.hx", "class Tagged {}");
+    common::git(&origin, &["tag", "v1"]);
+    common::git_commit_file(&origin, "Later.hx", "class Later {}");
+    let bare = project.dir.path().join("origin.git");
+    common::git(
+        project.dir.path(),
+        &["clone", "-q", "--bare", "origin", "origin.git"],
+    );
+
+    // a plain path, cloned through the cache
+    let fixture = git_fixture(&bare, "v1");
+    install_command::install_from_git_using_gix_clone(&fixture).unwrap();
+    let clone = project.dir.path().join(".haxelib/fixture/git");
+    assert_eq!(common::git(&clone, &["rev-parse", "HEAD"]), tagged);
+    assert!(!clone.join("Later.hx").exists());
+    assert_eq!(
+        check_command::check_dependency(&fixture, &project.json_path())
+            .unwrap()
+            .install_type,
+        check_command::InstallType::AlreadyInstalled
+    );
+
+    // a file:// url, cloned shallow straight from the repo
+    let mut shallow = git_fixture(&bare, "v1");
+    shallow.name = "shallow".to_string();
+    shallow.url = Some(format!("file://{}", bare.display()));
+    shallow.shallow = Some(true);
+    install_command::install_from_git_using_gix_clone(&shallow).unwrap();
+    let clone = project.dir.path().join(".haxelib/shallow/git");
+    assert!(clone.join(".git/shallow").exists());
+    assert_eq!(common::git(&clone, &["rev-parse", "HEAD"]), tagged);
+}
+
+#[test]
+fn test_git_command() {
+    let project = TempProject::new();
+    std::fs::write(project.json_path(), "{\"dependencies\": []}").unwrap();
+    let origin = project.dir.path().join("origin");
+    let first = common::init_git_repo(&origin);
+    common::git(&origin, &["tag", "v1"]);
+    let latest = common::git_commit_file(&origin, "Latest.hx", "class Latest {}");
+    let other = project.dir.path().join("other");
+    common::init_git_repo(&other);
+    let other_latest = common::git_commit_file(&other, "Other.hx", "class Other {}");
+
+    let clone = project.dir.path().join(".haxelib/fixture/git");
+    let install = |url: &Path, vcs_ref: Option<&str>| {
+        let deps = hmm::json::read_json(&project.json_path()).unwrap();
+        let url = url.display().to_string();
+        let vcs_ref = vcs_ref.map(str::to_string);
+        git_command::install_git("fixture", &url, &vcs_ref, false, deps, project.json_path())
+            .unwrap();
+
+        let deps = hmm::json::read_json(&project.json_path()).unwrap();
+        let fixture = deps.get_haxelib("fixture").unwrap();
+        (
+            fixture.url.clone().unwrap(),
+            fixture.vcs_ref.clone().unwrap(),
+            common::git(&clone, &["rev-parse", "HEAD"]),
+        )
+    };
+
+    // a new dependency
+    let origin_url = origin.display().to_string();
+    assert_eq!(
+        install(&origin, Some("v1")),
+        (origin_url.clone(), "v1".to_string(), first)
+    );
+
+    // re-run without a ref, so it goes back to the default branch
+    assert_eq!(
+        install(&origin, None),
+        (origin_url, "master".to_string(), latest)
+    );
+
+    // a different url replaces the clone
+    assert_eq!(
+        install(&other, None),
+        (
+            other.display().to_string(),
+            "master".to_string(),
+            other_latest
+        )
+    );
+    assert!(clone.join("Other.hx").exists());
+    let deps = hmm::json::read_json(&project.json_path()).unwrap();
+    assert_eq!(deps.dependencies.len(), 1);
+}